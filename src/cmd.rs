@@ -2,7 +2,10 @@ use std::error::Error;
 
 use clap::{Parser, Subcommand};
 
+use crate::task::clock::ClockCommand;
 use crate::task::import::ImportCommand;
+use crate::task::list::ListCommand;
+use crate::task::migrate::MigrateCommand;
 use crate::task::rename::RenameCommand;
 use crate::task::tidyup::TidyupCommand;
 
@@ -18,6 +21,9 @@ pub type CmdResult = Result<(), Box<dyn Error>>;
 pub struct Cli {
     #[command(subcommand)]
     pub command: Option<Commands>,
+    #[arg(help = "config file, default $IPHOTO_CONFIG or ~/.config/iphoto/config.toml.")]
+    #[arg(long, global = true)]
+    pub config: Option<String>,
 }
 
 #[derive(Subcommand)]
@@ -30,6 +36,12 @@ pub enum Commands {
     Rename2(Rename2Command),
     #[command(about = "Tidyup photos in the directories")]
     Tidyup(TidyupCommand),
+    #[command(about = "Work out a camera clock correction from two photos of one moment")]
+    Clock(ClockCommand),
+    #[command(about = "Rename V1 and compact names to V2, with their sidecars")]
+    Migrate(MigrateCommand),
+    #[command(about = "List capture time, body and exposure of photos and videos")]
+    List(ListCommand),
     
 }
 
@@ -39,12 +51,16 @@ pub trait Cmd {
 
 pub fn run() -> CmdResult {
     let cli = Cli::parse();
+    crate::core::config::load(cli.config.as_deref())?;
 
     match cli.command {
         Some(Commands::Import(cmd)) => cmd.run(),
         Some(Commands::Rename(cmd)) => cmd.run(),
         Some(Commands::Rename2(cmd)) => cmd.run(),
         Some(Commands::Tidyup(cmd)) => cmd.run(),
+        Some(Commands::Clock(cmd)) => cmd.run(),
+        Some(Commands::Migrate(cmd)) => cmd.run(),
+        Some(Commands::List(cmd)) => cmd.run(),
         _ => Ok(()),
     }
 }
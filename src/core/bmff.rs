@@ -0,0 +1,293 @@
+use std::fs::File;
+use std::io::{self, Read, Seek, SeekFrom};
+use std::path::Path;
+use std::time::Duration;
+
+use chrono::{DateTime, FixedOffset, TimeZone, Utc};
+use lazy_static::lazy_static;
+use regex::Regex;
+
+/// Largest `moov` read into memory; real ones are well under a megabyte.
+const MAX_MOOV: u64 = 64 << 20;
+/// Seconds from the ISO-BMFF epoch, 1904-01-01 UTC, to the Unix epoch.
+const EPOCH_1904: i64 = 2_082_844_800;
+
+lazy_static! {
+    static ref XML_MODEL: Regex = Regex::new(r#"modelName="([^"]+)""#).unwrap();
+    static ref XML_MAKE: Regex = Regex::new(r#"manufacturer="([^"]+)""#).unwrap();
+    static ref XML_CREATED: Regex = Regex::new(r#"<CreationDate\s+value="([^"]+)""#).unwrap();
+}
+
+/// What an MP4/MOV says about its capture, from `moov`.
+#[derive(Debug, Default, PartialEq)]
+pub struct Movie {
+    /// `mvhd` creation time, UTC by the spec.
+    pub created: Option<DateTime<Utc>>,
+    /// Creation time with the camera's offset, from Apple keys or Sony XML.
+    pub local: Option<DateTime<FixedOffset>>,
+    pub make: Option<String>,
+    pub model: Option<String>,
+    pub duration: Option<Duration>,
+}
+
+fn be_u16(b: &[u8], at: usize) -> Option<u16> {
+    Some(u16::from_be_bytes(b.get(at..at + 2)?.try_into().ok()?))
+}
+
+fn be_u32(b: &[u8], at: usize) -> Option<u32> {
+    Some(u32::from_be_bytes(b.get(at..at + 4)?.try_into().ok()?))
+}
+
+fn be_u64(b: &[u8], at: usize) -> Option<u64> {
+    Some(u64::from_be_bytes(b.get(at..at + 8)?.try_into().ok()?))
+}
+
+/// Child boxes of `data` as (type, payload).
+fn boxes(data: &[u8]) -> Vec<([u8; 4], &[u8])> {
+    let mut out = Vec::new();
+    let mut at = 0;
+    while at + 8 <= data.len() {
+        let size = be_u32(data, at).unwrap() as u64;
+        let kind: [u8; 4] = data[at + 4..at + 8].try_into().unwrap();
+        let (header, size) = match size {
+            0 => (8, (data.len() - at) as u64),
+            1 => match be_u64(data, at + 8) {
+                Some(size) => (16, size),
+                None => break,
+            },
+            _ => (8, size),
+        };
+        if size < header as u64 || at as u64 + size > data.len() as u64 {
+            break;
+        }
+        out.push((kind, &data[at + header..at + size as usize]));
+        at += size as usize;
+    }
+    out
+}
+
+fn child<'a>(data: &'a [u8], kind: &[u8; 4]) -> Option<&'a [u8]> {
+    boxes(data).into_iter().find(|(k, _)| k == kind).map(|(_, b)| b)
+}
+
+fn text(b: &[u8]) -> Option<String> {
+    let s = String::from_utf8_lossy(b).trim_matches(char::from(0)).trim().to_string();
+    (!s.is_empty()).then_some(s)
+}
+
+fn parse_local(s: &str) -> Option<DateTime<FixedOffset>> {
+    DateTime::parse_from_rfc3339(s)
+        .or_else(|_| DateTime::parse_from_str(s, "%Y-%m-%dT%H:%M:%S%z"))
+        .ok()
+}
+
+fn parse_mvhd(b: &[u8], movie: &mut Movie) -> Option<()> {
+    let (created, timescale, duration) = match b.first()? {
+        1 => (be_u64(b, 4)?, be_u32(b, 20)?, be_u64(b, 24)?),
+        _ => (be_u32(b, 4)? as u64, be_u32(b, 12)?, be_u32(b, 16)? as u64),
+    };
+    if created != 0 {
+        movie.created = Utc.timestamp_opt(created as i64 - EPOCH_1904, 0).single();
+    }
+    if timescale != 0 {
+        movie.duration = Some(Duration::from_secs_f64(duration as f64 / timescale as f64));
+    }
+    Some(())
+}
+
+/// A `©mak`-style item: iTunes `data` box or QuickTime length-prefixed text.
+fn item_text(b: &[u8]) -> Option<String> {
+    match child(b, b"data") {
+        Some(data) => text(data.get(8..)?),
+        None => text(b.get(4..4 + be_u16(b, 0)? as usize)?),
+    }
+}
+
+/// Nikon `NCDT/NCTG`: tagged entries, tag 1 is the make and 2 the model.
+fn parse_nctg(b: &[u8], movie: &mut Movie) {
+    let mut at = 0;
+    while let (Some(tag), Some(kind), Some(count)) = (be_u32(b, at), be_u16(b, at + 4), be_u16(b, at + 6)) {
+        let size = match kind {
+            3 => 2,
+            4 | 9 => 4,
+            5 | 10 => 8,
+            _ => 1,
+        } * count as usize;
+        let Some(value) = b.get(at + 8..at + 8 + size) else {
+            break;
+        };
+        match (tag, kind) {
+            (1, 2) => movie.make = movie.make.take().or_else(|| text(value)),
+            (2, 2) => movie.model = movie.model.take().or_else(|| text(value)),
+            _ => (),
+        }
+        at += 8 + size;
+    }
+}
+
+fn parse_udta(b: &[u8], movie: &mut Movie) {
+    for (kind, body) in boxes(b) {
+        match &kind {
+            b"\xa9mak" => movie.make = movie.make.take().or_else(|| item_text(body)),
+            b"\xa9mod" => movie.model = movie.model.take().or_else(|| item_text(body)),
+            b"NCDT" => {
+                if let Some(nctg) = child(body, b"NCTG") {
+                    parse_nctg(nctg, movie);
+                }
+            }
+            b"meta" => parse_meta(body, movie),
+            _ => (),
+        }
+    }
+}
+
+fn parse_xml(xml: &str, movie: &mut Movie) {
+    let get = |re: &Regex| re.captures(xml).map(|c| c[1].to_string());
+    movie.make = movie.make.take().or_else(|| get(&XML_MAKE));
+    movie.model = movie.model.take().or_else(|| get(&XML_MODEL));
+    movie.local = movie.local.or_else(|| get(&XML_CREATED).and_then(|x| parse_local(&x)));
+}
+
+/// `meta` holding Apple `keys`/`ilst`, iTunes items or Sony's XML.
+fn parse_meta(b: &[u8], movie: &mut Movie) {
+    // ISO meta is a full box; the QuickTime one starts with its children
+    let b = match b.get(4..8) {
+        Some(b"hdlr") => b,
+        _ => b.get(4..).unwrap_or_default(),
+    };
+    let keys: Vec<String> = child(b, b"keys")
+        .map(|k| {
+            boxes(k.get(8..).unwrap_or_default())
+                .into_iter()
+                .map(|(_, name)| String::from_utf8_lossy(name).to_string())
+                .collect()
+        })
+        .unwrap_or_default();
+    if let Some(ilst) = child(b, b"ilst") {
+        for (kind, body) in boxes(ilst) {
+            let index = u32::from_be_bytes(kind) as usize;
+            let key = keys.get(index.wrapping_sub(1)).map(|x| x.as_str());
+            let value = || item_text(body);
+            match (key, &kind) {
+                (Some("com.apple.quicktime.make"), _) | (_, b"\xa9mak") => {
+                    movie.make = movie.make.take().or_else(value)
+                }
+                (Some("com.apple.quicktime.model"), _) | (_, b"\xa9mod") => {
+                    movie.model = movie.model.take().or_else(value)
+                }
+                (Some("com.apple.quicktime.creationdate"), _) => {
+                    movie.local = movie.local.or_else(|| value().and_then(|x| parse_local(&x)))
+                }
+                _ => (),
+            }
+        }
+    }
+    if let Some(xml) = child(b, b"xml ") {
+        parse_xml(&String::from_utf8_lossy(xml.get(4..).unwrap_or_default()), movie);
+    }
+}
+
+pub fn parse_moov(moov: &[u8]) -> Movie {
+    let mut movie = Movie::default();
+    for (kind, body) in boxes(moov) {
+        match &kind {
+            b"mvhd" => {
+                parse_mvhd(body, &mut movie);
+            }
+            b"udta" => parse_udta(body, &mut movie),
+            b"meta" => parse_meta(body, &mut movie),
+            _ => (),
+        }
+    }
+    movie
+}
+
+/// Find `moov` among the top-level boxes, seeking over `mdat`.
+fn read_moov(file: &mut File) -> io::Result<Option<Vec<u8>>> {
+    let len = file.metadata()?.len();
+    let mut at = 0;
+    let mut header = [0u8; 16];
+    while at + 8 <= len {
+        file.seek(SeekFrom::Start(at))?;
+        file.read_exact(&mut header[..8])?;
+        let (mut size, mut skip) = (be_u32(&header, 0).unwrap() as u64, 8);
+        if size == 1 {
+            file.read_exact(&mut header[8..16])?;
+            size = be_u64(&header, 8).unwrap();
+            skip = 16;
+        } else if size == 0 {
+            size = len - at;
+        }
+        if size < skip {
+            break;
+        }
+        if &header[4..8] == b"moov" {
+            if size > MAX_MOOV {
+                return Err(io::Error::other("moov too large"));
+            }
+            let mut moov = vec![0; (size - skip) as usize];
+            file.read_exact(&mut moov)?;
+            return Ok(Some(moov));
+        }
+        at += size;
+    }
+    Ok(None)
+}
+
+/// Read `path`, and for Sony clips the `M01.XML` next to it when the
+/// movie itself lacks a model.
+pub fn read<P: AsRef<Path>>(path: P) -> io::Result<Movie> {
+    let path = path.as_ref();
+    let mut file = File::open(path)?;
+    let moov = read_moov(&mut file)?.ok_or_else(|| io::Error::other("no moov box"))?;
+    let mut movie = parse_moov(&moov);
+    if movie.model.is_none() {
+        if let (Some(dir), Some(stem)) = (path.parent(), path.file_stem()) {
+            let xml = dir.join(format!("{}M01.XML", stem.to_string_lossy()));
+            if let Ok(xml) = std::fs::read_to_string(xml) {
+                parse_xml(&xml, &mut movie);
+            }
+        }
+    }
+    Ok(movie)
+}
+
+/// A box of `kind` around `body`, for building files in tests.
+#[cfg(test)]
+pub(crate) fn mk(kind: &[u8; 4], body: &[u8]) -> Vec<u8> {
+    let mut b = ((body.len() + 8) as u32).to_be_bytes().to_vec();
+    b.extend_from_slice(kind);
+    b.extend_from_slice(body);
+    b
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_moov() {
+        // mvhd v0: created 2023-01-05 06:01:08 UTC, 10 s at timescale 1000
+        let mut mvhd = vec![0u8; 4];
+        mvhd.extend((1672898468 + EPOCH_1904 as u32).to_be_bytes());
+        mvhd.extend(0u32.to_be_bytes());
+        mvhd.extend(1000u32.to_be_bytes());
+        mvhd.extend(10_000u32.to_be_bytes());
+        let model = [&[0, 6, 0, 0][..], b"ILCE-1"].concat();
+        let udta = mk(b"udta", &[mk(b"\xa9mod", &model), mk(b"\xa9mak", b"\0\x04\0\0Sony")].concat());
+        let moov = [mk(b"mvhd", &mvhd), udta].concat();
+
+        let movie = parse_moov(&moov);
+        assert_eq!(movie.created.unwrap().to_rfc3339(), "2023-01-05T06:01:08+00:00");
+        assert_eq!(movie.duration, Some(Duration::from_secs(10)));
+        assert_eq!(movie.make.as_deref(), Some("Sony"));
+        assert_eq!(movie.model.as_deref(), Some("ILCE-1"));
+
+        let xml = r#"<NonRealTimeMeta><CreationDate value="2023-01-05T15:01:08+09:00"/>
+            <Device manufacturer="Sony" modelName="ILCE-7RM4A"/></NonRealTimeMeta>"#;
+        let mut movie = Movie::default();
+        parse_xml(xml, &mut movie);
+        assert_eq!(movie.model.as_deref(), Some("ILCE-7RM4A"));
+        assert_eq!(movie.local.unwrap().to_rfc3339(), "2023-01-05T15:01:08+09:00");
+    }
+}
@@ -0,0 +1,177 @@
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use filetime::FileTime;
+use sha2::Digest;
+use walkdir::WalkDir;
+
+pub const CARDS_DIR: &str = ".iphoto-cards";
+
+/// How many of the earliest files identify a card without a volume UUID.
+const EARLIEST_FILES: usize = 8;
+
+/// The DCIM folder of a source given as the card root or as DCIM itself.
+fn dcim_of(source: &Path) -> PathBuf {
+    if source.ends_with("DCIM") {
+        source.to_path_buf()
+    } else {
+        source.join("DCIM")
+    }
+}
+
+/// Identify a card by its volume UUID and where on the volume the import
+/// reads from (`volume` is the UUID and mount point); neither changes as
+/// the camera adds folders. Without a UUID, the earliest files under DCIM
+/// stand in, as they stay put until the card is formatted. `None` when
+/// there is nothing to go by.
+pub fn card_id(volume: Option<(&str, &Path)>, source: &Path) -> Option<String> {
+    let mut hasher = sha2::Sha256::new();
+    if let Some((uuid, point)) = volume {
+        let root = dcim_of(source);
+        let rel = root.strip_prefix(point).unwrap_or(&root);
+        hasher.update(uuid);
+        hasher.update("\n");
+        hasher.update(rel.to_string_lossy().as_bytes());
+    } else {
+        let dcim = dcim_of(source);
+        let mut files: Vec<(i64, String, u64)> = WalkDir::new(&dcim)
+            .into_iter()
+            .filter_entry(|e| !e.file_name().to_string_lossy().starts_with('.'))
+            .flatten()
+            .filter(|e| e.file_type().is_file())
+            .filter_map(|e| {
+                let (size, mtime) = file_stamp(&e.metadata().ok()?);
+                let rel = e.path().strip_prefix(&dcim).ok()?;
+                Some((mtime, rel.to_string_lossy().to_string(), size))
+            })
+            .collect();
+        if files.is_empty() {
+            return None;
+        }
+        files.sort();
+        for (mtime, rel, size) in files.iter().take(EARLIEST_FILES) {
+            hasher.update(format!("{mtime}\t{size}\t{rel}\n"));
+        }
+    }
+    let digest = hasher.finalize();
+    Some(digest[..8].iter().map(|b| format!("{b:02x}")).collect())
+}
+
+pub fn file_stamp(meta: &fs::Metadata) -> (u64, i64) {
+    (
+        meta.len(),
+        FileTime::from_last_modification_time(meta).unix_seconds(),
+    )
+}
+
+/// Source files already imported from one card, keyed by their path
+/// relative to the card root, with size and mtime at import time.
+pub struct CardState {
+    path: PathBuf,
+    files: HashMap<String, (u64, i64)>,
+}
+
+impl CardState {
+    pub fn load<P: AsRef<Path>>(root: P, id: &str) -> io::Result<CardState> {
+        let path = root.as_ref().join(CARDS_DIR).join(id);
+        let files = match fs::read_to_string(&path) {
+            Ok(text) => parse(&text),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => HashMap::new(),
+            Err(e) => return Err(e),
+        };
+        Ok(CardState { path, files })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.files.is_empty()
+    }
+
+    pub fn contains(&self, rel: &str, stamp: (u64, i64)) -> bool {
+        self.files.get(rel) == Some(&stamp)
+    }
+
+    pub fn insert(&mut self, rel: &str, stamp: (u64, i64)) {
+        self.files.insert(rel.to_string(), stamp);
+    }
+
+    pub fn save(&self) -> io::Result<()> {
+        if let Some(parent) = self.path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let mut text = String::new();
+        for (rel, (size, mtime)) in &self.files {
+            text.push_str(&format!("{size}\t{mtime}\t{rel}\n"));
+        }
+        fs::write(&self.path, text)
+    }
+}
+
+fn parse(text: &str) -> HashMap<String, (u64, i64)> {
+    let mut files = HashMap::new();
+    for line in text.lines() {
+        let fields: Vec<&str> = line.splitn(3, '\t').collect();
+        let [size, mtime, rel] = fields[..] else {
+            continue;
+        };
+        if let (Ok(size), Ok(mtime)) = (size.parse(), mtime.parse()) {
+            files.insert(rel.to_string(), (size, mtime));
+        }
+    }
+    files
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn touch(path: &Path, mtime: i64) {
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, path.to_string_lossy().as_bytes()).unwrap();
+        filetime::set_file_mtime(path, FileTime::from_unix_time(mtime, 0)).unwrap();
+    }
+
+    #[test]
+    fn test_card_id() {
+        let card = std::env::temp_dir().join(format!("iphoto-cardid-{}", std::process::id()));
+        let volume = Some(("1234-ABCD", card.as_path()));
+        assert_eq!(card_id(None, &card), None);
+        for i in 0..EARLIEST_FILES {
+            touch(&card.join(format!("DCIM/100MSDCF/DSC{i:05}.ARW")), 1_672_898_468 + i as i64);
+        }
+
+        let by_uuid = card_id(volume, &card).unwrap();
+        let by_files = card_id(None, &card).unwrap();
+        assert_eq!(card_id(volume, &card.join("DCIM")).unwrap(), by_uuid);
+        assert_eq!(card_id(None, &card.join("DCIM")).unwrap(), by_files);
+        assert_ne!(card_id(Some(("5678-EF01", card.as_path())), &card).unwrap(), by_uuid);
+
+        // shooting more does not change the id
+        touch(&card.join("DCIM/101MSDCF/DSC00100.ARW"), 1_672_900_000);
+        assert_eq!(card_id(volume, &card).unwrap(), by_uuid);
+        assert_eq!(card_id(None, &card).unwrap(), by_files);
+
+        // a formatted card starts over
+        fs::remove_dir_all(card.join("DCIM/100MSDCF")).unwrap();
+        assert_ne!(card_id(None, &card).unwrap(), by_files);
+        fs::remove_dir_all(&card).unwrap();
+    }
+
+    #[test]
+    fn test_state_round_trip() {
+        let root = std::env::temp_dir().join(format!("iphoto-cardstate-{}", std::process::id()));
+        let mut state = CardState::load(&root, "0123abcd").unwrap();
+        assert!(state.is_empty());
+        state.insert("100MSDCF/DSC00001.ARW", (24_000_000, 1_672_898_468));
+        state.insert("100MSDCF/name\twith tab.JPG", (3_000_000, 1_672_898_470));
+        state.save().unwrap();
+
+        let state = CardState::load(&root, "0123abcd").unwrap();
+        assert!(state.contains("100MSDCF/DSC00001.ARW", (24_000_000, 1_672_898_468)));
+        assert!(state.contains("100MSDCF/name\twith tab.JPG", (3_000_000, 1_672_898_470)));
+        assert!(!state.contains("100MSDCF/DSC00001.ARW", (24_000_000, 1_672_898_469)));
+        assert!(CardState::load(&root, "other").unwrap().is_empty());
+        fs::remove_dir_all(&root).unwrap();
+    }
+}
@@ -0,0 +1,123 @@
+use std::fs::File;
+use std::io::{self, Read, Write};
+use std::path::Path;
+
+use sha2::{Digest, Sha256};
+
+const BUF_SIZE: usize = 1 << 20;
+
+pub type Sum = [u8; 32];
+
+pub fn to_hex(sum: &Sum) -> String {
+    sum.iter().map(|b| format!("{b:02x}")).collect()
+}
+
+pub fn file_sum<P: AsRef<Path>>(path: P) -> io::Result<Sum> {
+    let mut file = File::open(path)?;
+    let mut hasher = Sha256::new();
+    let mut buf = vec![0u8; BUF_SIZE];
+    loop {
+        let n = file.read(&mut buf)?;
+        if n == 0 {
+            break;
+        }
+        hasher.update(&buf[..n]);
+    }
+    Ok(hasher.finalize().into())
+}
+
+/// Copy `src` to `dest`, hashing the source while it is read.
+/// Returns the number of bytes written and the digest of what was read.
+pub fn copy_with_sum<P: AsRef<Path>, Q: AsRef<Path>>(src: P, dest: Q) -> io::Result<(u64, Sum)> {
+    let mut reader = File::open(src)?;
+    let mut writer = File::create(dest)?;
+    let mut hasher = Sha256::new();
+    let mut buf = vec![0u8; BUF_SIZE];
+    let mut total: u64 = 0;
+    loop {
+        let n = reader.read(&mut buf)?;
+        if n == 0 {
+            break;
+        }
+        hasher.update(&buf[..n]);
+        writer.write_all(&buf[..n])?;
+        total += n as u64;
+    }
+    writer.flush()?;
+    Ok((total, hasher.finalize().into()))
+}
+
+/// Copy `src` to every path in `dests` with a single read of the source.
+/// A destination that fails to open or write is dropped and its error kept
+/// in its slot of the returned list; the other destinations carry on.
+pub fn copy_to_all<P: AsRef<Path>>(
+    src: P,
+    dests: &[&Path],
+) -> io::Result<(u64, Sum, Vec<io::Result<()>>)> {
+    let mut reader = File::open(src)?;
+    let mut writers: Vec<io::Result<File>> = dests.iter().map(File::create).collect();
+    let mut hasher = Sha256::new();
+    let mut buf = vec![0u8; BUF_SIZE];
+    let mut total: u64 = 0;
+    loop {
+        let n = reader.read(&mut buf)?;
+        if n == 0 {
+            break;
+        }
+        hasher.update(&buf[..n]);
+        for writer in writers.iter_mut() {
+            if let Ok(file) = writer {
+                if let Err(e) = file.write_all(&buf[..n]) {
+                    *writer = Err(e);
+                }
+            }
+        }
+        total += n as u64;
+    }
+    let written = writers
+        .into_iter()
+        .map(|w| w.and_then(|mut file| file.flush()))
+        .collect();
+    Ok((total, hasher.finalize().into(), written))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use hex_literal::hex;
+
+    #[test]
+    fn test_copy_with_sum() {
+        let dir = std::env::temp_dir().join(format!("iphoto-checksum-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let src = dir.join("src.bin");
+        let dest = dir.join("dest.bin");
+        std::fs::write(&src, b"hello world").unwrap();
+
+        let (n, sum) = copy_with_sum(&src, &dest).unwrap();
+        assert_eq!(n, 11);
+        assert_eq!(sum, file_sum(&dest).unwrap());
+        assert_eq!(
+            sum,
+            hex!("b94d27b9934d3e08a52e52d7da7dabfac484efe37a5380ee9088f7ace2efcde9")
+        );
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_copy_to_all_keeps_going() {
+        let dir = std::env::temp_dir().join(format!("iphoto-checksum-all-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let src = dir.join("src.bin");
+        let good = dir.join("good.bin");
+        let bad = dir.join("missing/bad.bin");
+        std::fs::write(&src, b"hello world").unwrap();
+
+        let (n, sum, written) = copy_to_all(&src, &[bad.as_path(), good.as_path()]).unwrap();
+        assert_eq!(n, 11);
+        assert!(written[0].is_err());
+        assert!(written[1].is_ok());
+        assert_eq!(sum, file_sum(&good).unwrap());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
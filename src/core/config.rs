@@ -0,0 +1,118 @@
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+use std::sync::OnceLock;
+
+use serde::Deserialize;
+
+use crate::core::utils;
+
+pub const CONFIG_ENV: &str = "IPHOTO_CONFIG";
+
+/// User settings, read once at startup from `--config`, `$IPHOTO_CONFIG`
+/// or `~/.config/iphoto/config.toml`.
+///
+/// ```toml
+/// raw_models = true
+/// millis = true
+/// template = "{year}/{date}/{name}.{ext}"
+///
+/// [models]
+/// "ILCE-7M4" = "A7M4"
+///
+/// [bodies]
+/// "5012345" = "A1a"
+/// "5067890" = "A1b"
+///
+/// [clock]
+/// A7R4A = -192
+/// A1b = 3
+/// ```
+#[derive(Deserialize, Default, Debug)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    /// EXIF `Model` to the short name used in file names, merged over the
+    /// built-in map.
+    pub models: HashMap<String, String>,
+    /// Body serial number to the short name used instead of the model's,
+    /// to tell apart bodies of the same model.
+    pub bodies: HashMap<String, String>,
+    /// Name unknown bodies after their sanitised EXIF `Model` instead of
+    /// `UNSET`.
+    pub raw_models: bool,
+    /// Add the milliseconds of the capture time to names,
+    /// `20230105_150108_120__03212__A1`, so bursts sort by frame.
+    pub millis: bool,
+    /// Naming layout for import and tidyup, see `template::Template`.
+    /// rename and rename2 use its file name part.
+    pub template: Option<String>,
+    /// Seconds to add to the capture time of each body, by short model
+    /// or body name, to correct a clock that runs ahead or behind.
+    pub clock: HashMap<String, i64>,
+}
+
+#[derive(thiserror::Error, Debug)]
+pub enum ConfigError {
+    #[error("config-error {0}: {1}")]
+    Io(String, String),
+    #[error("config-error {0}: {1}")]
+    Parse(String, String),
+}
+
+static CONFIG: OnceLock<Config> = OnceLock::new();
+
+fn default_path() -> Option<PathBuf> {
+    if let Some(path) = utils::env_var(CONFIG_ENV) {
+        return Some(PathBuf::from(path));
+    }
+    let home = utils::env_var("HOME")?;
+    Some(PathBuf::from(home).join(".config/iphoto/config.toml"))
+}
+
+pub fn parse(text: &str) -> Result<Config, toml::de::Error> {
+    toml::from_str(text)
+}
+
+/// Load the config file. An explicit `path` must exist; the default one
+/// may be missing.
+pub fn load(path: Option<&str>) -> Result<(), ConfigError> {
+    let (path, required) = match path {
+        Some(path) => (Some(PathBuf::from(path)), true),
+        None => (default_path(), false),
+    };
+    let config = match &path {
+        Some(path) => {
+            let path_str = path.display().to_string();
+            match fs::read_to_string(path) {
+                Ok(text) => parse(&text).map_err(|e| ConfigError::Parse(path_str, e.message().to_string()))?,
+                Err(e) if e.kind() == io::ErrorKind::NotFound && !required => Config::default(),
+                Err(e) => return Err(ConfigError::Io(path_str, e.to_string())),
+            }
+        }
+        None => Config::default(),
+    };
+    let _ = CONFIG.set(config);
+    Ok(())
+}
+
+/// The loaded config, or the defaults when `load` was never called.
+pub fn get() -> &'static Config {
+    CONFIG.get_or_init(Config::default)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_config() {
+        let config = parse("raw_models = true\nmillis = true\n[models]\n\"ILCE-7M4\" = \"A7M4\"\n").unwrap();
+        assert!(config.raw_models && config.millis);
+        assert_eq!(config.models["ILCE-7M4"], "A7M4");
+
+        let config = parse("").unwrap();
+        assert!(!config.raw_models && config.models.is_empty());
+        assert!(parse("model = 1").is_err());
+    }
+}
@@ -0,0 +1,263 @@
+use std::fmt;
+use std::io::Read;
+use std::path::Path;
+
+use chrono::{DateTime, FixedOffset, NaiveDate, NaiveDateTime, TimeZone, Timelike, Utc};
+use exif::{In, Tag, Value};
+use lazy_static::lazy_static;
+use regex::Regex;
+
+use crate::core::fninfo::{exif_ascii, parse_subsec};
+use crate::core::zone;
+
+/// How much of a file is searched for an embedded XMP packet.
+const XMP_SCAN: u64 = 1 << 20;
+
+lazy_static! {
+    static ref XMP_DATE: Regex =
+        Regex::new(r#"photoshop:DateCreated(?:="|>)([^"<]+)"#).unwrap();
+    static ref DATE_IN_NAME: Regex = Regex::new(
+        r"(?:^|\D)((?:19|20)\d{2})-?(\d{2})-?(\d{2})(?:[ _T-]+(?:at )?(\d{2})[.:-]?(\d{2})[.:-]?(\d{2}))?"
+    )
+    .unwrap();
+    static ref DATE_DIR: Regex = Regex::new(r"^((?:19|20)\d{2})-?(\d{2})-?(\d{2})(?:\D|$)").unwrap();
+}
+
+/// Where the capture time of an `Info` came from, best first.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DateSource {
+    /// A name this tool gave the file earlier.
+    Named,
+    /// EXIF `DateTimeOriginal`.
+    Original,
+    /// EXIF `DateTimeDigitized`, what exiftool calls `CreateDate`.
+    Digitized,
+    /// The movie header of a video.
+    Movie,
+    /// EXIF `GPSDateStamp` and `GPSTimeStamp`, in UTC.
+    Gps,
+    /// XMP `photoshop:DateCreated`, in the file or a sidecar.
+    Xmp,
+    /// A date in a name the tool did not give, such as a screenshot's.
+    FileName,
+    /// A date naming the directory holding the file.
+    ParentDir,
+    /// The file's modification time.
+    Mtime,
+}
+
+impl DateSource {
+    /// Read from the camera's own clock, so clock corrections apply.
+    pub fn is_camera_clock(&self) -> bool {
+        matches!(self, DateSource::Original | DateSource::Digitized | DateSource::Movie)
+    }
+
+    /// Recorded inside the file, rather than guessed from where it lies.
+    pub fn is_embedded(&self) -> bool {
+        matches!(
+            self,
+            DateSource::Original | DateSource::Digitized | DateSource::Movie | DateSource::Gps | DateSource::Xmp
+        )
+    }
+
+    /// Anything but a file name of ours or the time the camera recorded
+    /// for the shot.
+    pub fn is_fallback(&self) -> bool {
+        !matches!(self, DateSource::Named | DateSource::Original | DateSource::Movie)
+    }
+}
+
+impl fmt::Display for DateSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            DateSource::Named => "named",
+            DateSource::Original => "exif-original",
+            DateSource::Digitized => "exif-digitized",
+            DateSource::Movie => "movie",
+            DateSource::Gps => "gps",
+            DateSource::Xmp => "xmp",
+            DateSource::FileName => "file-name",
+            DateSource::ParentDir => "parent-dir",
+            DateSource::Mtime => "mtime",
+        })
+    }
+}
+
+/// A capture time and where it was found.
+pub struct Found {
+    pub datetime: NaiveDateTime,
+    pub millis: Option<u16>,
+    pub offset: Option<FixedOffset>,
+    pub source: DateSource,
+}
+
+impl Found {
+    fn naive(datetime: NaiveDateTime, source: DateSource) -> Found {
+        Found {
+            datetime,
+            millis: None,
+            offset: None,
+            source,
+        }
+    }
+
+    /// A time with its offset, kept as the wall time at that offset.
+    pub fn zoned<Tz: TimeZone>(time: DateTime<Tz>, source: DateSource) -> Found {
+        let time = time.fixed_offset();
+        let millis = (time.nanosecond() / 1_000_000) as u16;
+        Found {
+            datetime: time.naive_local().with_nanosecond(0).unwrap(),
+            millis: (millis != 0).then_some(millis),
+            offset: Some(*time.offset()),
+            source,
+        }
+    }
+}
+
+fn exif_time(exif: &exif::Exif, time: Tag, subsec: Tag, offset: Tag, source: DateSource) -> Option<Found> {
+    let datetime = NaiveDateTime::parse_from_str(&exif_ascii(exif, time)?, "%Y:%m:%d %H:%M:%S").ok()?;
+    Some(Found {
+        datetime,
+        millis: exif_ascii(exif, subsec).and_then(|x| parse_subsec(&x)),
+        offset: exif_ascii(exif, offset).and_then(|x| zone::parse_offset(&x)),
+        source,
+    })
+}
+
+/// GPS time is UTC; it is shown in the `--tz` zone.
+fn gps_time(exif: &exif::Exif) -> Option<Found> {
+    let date = NaiveDate::parse_from_str(&exif_ascii(exif, Tag::GPSDateStamp)?, "%Y:%m:%d").ok()?;
+    let Value::Rational(ref hms) = exif.get_field(Tag::GPSTimeStamp, In::PRIMARY)?.value else {
+        return None;
+    };
+    let [h, m, s] = hms.get(..3)? else {
+        return None;
+    };
+    let seconds = h.to_f64() * 3600.0 + m.to_f64() * 60.0 + s.to_f64();
+    if !(0.0..86400.0).contains(&seconds) {
+        return None;
+    }
+    let utc = date.and_hms_opt(0, 0, 0)? + chrono::Duration::milliseconds((seconds * 1000.0).round() as i64);
+    Some(Found::zoned(zone::get().wall_clock(Utc.from_utc_datetime(&utc)), DateSource::Gps))
+}
+
+fn parse_xmp_date(s: &str) -> Option<Found> {
+    let s = s.trim();
+    if let Ok(time) = DateTime::parse_from_rfc3339(s) {
+        return Some(Found::zoned(time, DateSource::Xmp));
+    }
+    let datetime = NaiveDateTime::parse_from_str(s, "%Y-%m-%dT%H:%M:%S%.f")
+        .or_else(|_| NaiveDateTime::parse_from_str(s, "%Y-%m-%dT%H:%M"))
+        .or_else(|_| NaiveDate::parse_from_str(s, "%Y-%m-%d").map(|x| x.and_hms_opt(0, 0, 0).unwrap()))
+        .ok()?;
+    let millis = (datetime.nanosecond() / 1_000_000) as u16;
+    Some(Found {
+        millis: (millis != 0).then_some(millis),
+        ..Found::naive(datetime.with_nanosecond(0)?, DateSource::Xmp)
+    })
+}
+
+/// XMP in the first megabyte of the file, else in an `.xmp` sidecar.
+fn xmp_time(path: &Path) -> Option<Found> {
+    let mut head = Vec::new();
+    let embedded = std::fs::File::open(path)
+        .and_then(|x| x.take(XMP_SCAN).read_to_end(&mut head))
+        .ok()
+        .and_then(|_| XMP_DATE.captures(&String::from_utf8_lossy(&head)).map(|x| x[1].to_string()));
+    let date = embedded.or_else(|| {
+        ["xmp", "XMP"].iter().find_map(|ext| {
+            let xml = std::fs::read_to_string(path.with_extension(ext)).ok()?;
+            XMP_DATE.captures(&xml).map(|x| x[1].to_string())
+        })
+    })?;
+    parse_xmp_date(&date)
+}
+
+fn ymd(captures: &regex::Captures) -> Option<NaiveDate> {
+    let n = |i: usize| captures.get(i)?.as_str().parse::<u32>().ok();
+    NaiveDate::from_ymd_opt(n(1)? as i32, n(2)?, n(3)?)
+}
+
+/// `Screenshot 2023-01-05 at 15.01.08`, `IMG_20230105_150108`, `2023-01-05`.
+fn name_time(stem: &str) -> Option<NaiveDateTime> {
+    DATE_IN_NAME.captures_iter(stem).find_map(|c| {
+        let date = ymd(&c)?;
+        let n = |i: usize| c.get(i).map_or(Some(0), |x| x.as_str().parse::<u32>().ok());
+        date.and_hms_opt(n(4)?, n(5)?, n(6)?)
+    })
+}
+
+fn dir_time(path: &Path) -> Option<NaiveDateTime> {
+    let dir = path.parent()?.file_name()?.to_str()?;
+    ymd(&DATE_DIR.captures(dir)?)?.and_hms_opt(0, 0, 0)
+}
+
+/// On the `--tz` clock, as the naive time is read back in that zone.
+fn mtime(path: &Path) -> Option<NaiveDateTime> {
+    let modified = std::fs::metadata(path).ok()?.modified().ok()?;
+    zone::get().wall_clock(modified.into()).naive_local().with_nanosecond(0)
+}
+
+/// Capture time from EXIF `DateTimeOriginal`, or failing that from the
+/// first of the fallbacks in `DateSource` order that gives one.
+pub fn find(path: &Path, exif: Option<&exif::Exif>) -> Option<Found> {
+    if let Some(exif) = exif {
+        let found = exif_time(
+            exif,
+            Tag::DateTimeOriginal,
+            Tag::SubSecTimeOriginal,
+            Tag::OffsetTimeOriginal,
+            DateSource::Original,
+        )
+        .or_else(|| {
+            exif_time(
+                exif,
+                Tag::DateTimeDigitized,
+                Tag::SubSecTimeDigitized,
+                Tag::OffsetTimeDigitized,
+                DateSource::Digitized,
+            )
+        })
+        .or_else(|| gps_time(exif));
+        if found.is_some() {
+            return found;
+        }
+    }
+    find_outside(path)
+}
+
+/// The fallbacks that do not need EXIF: XMP, the names, then mtime.
+pub fn find_outside(path: &Path) -> Option<Found> {
+    xmp_time(path)
+        .or_else(|| {
+            let stem = path.file_stem()?.to_str()?;
+            name_time(stem).map(|x| Found::naive(x, DateSource::FileName))
+        })
+        .or_else(|| dir_time(path).map(|x| Found::naive(x, DateSource::ParentDir)))
+        .or_else(|| mtime(path).map(|x| Found::naive(x, DateSource::Mtime)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fallbacks() {
+        let t = |x: Option<NaiveDateTime>| x.map(|x| x.format("%Y%m%d_%H%M%S").to_string());
+        assert_eq!(t(name_time("Screenshot 2023-01-05 at 15.01.08")).as_deref(), Some("20230105_150108"));
+        assert_eq!(t(name_time("IMG_20230105_150108123")).as_deref(), Some("20230105_150108"));
+        assert_eq!(t(name_time("scan 2023-01-05")).as_deref(), Some("20230105_000000"));
+        assert_eq!(name_time("DSC03212"), None);
+        assert_eq!(name_time("IMG_20231399_150108"), None);
+        assert_eq!(
+            t(dir_time(Path::new("x/2023-01-05 Kyoto/a.jpg"))).as_deref(),
+            Some("20230105_000000")
+        );
+        assert_eq!(dir_time(Path::new("x/Kyoto/a.jpg")), None);
+
+        let found = parse_xmp_date("2023-01-05T15:01:08.12+09:00").unwrap();
+        assert_eq!(t(Some(found.datetime)).as_deref(), Some("20230105_150108"));
+        assert_eq!((found.millis, found.offset.map(|x| x.local_minus_utc())), (Some(120), Some(9 * 3600)));
+        assert_eq!(parse_xmp_date("2023-01-05").unwrap().source, DateSource::Xmp);
+    }
+}
@@ -0,0 +1,138 @@
+use exif::{In, Tag, Value};
+use serde::Serialize;
+
+use crate::core::fninfo::exif_ascii;
+
+/// Optics and exposure settings of a shot, as EXIF records them.
+#[derive(Clone, Debug, Default, PartialEq, Serialize)]
+pub struct Exposure {
+    /// `LensModel`
+    pub lens: Option<String>,
+    /// `LensSpecification`: shortest and longest focal length, in mm, and
+    /// the widest aperture at each. Unknown parts are `None`.
+    pub lens_spec: Option<[Option<f64>; 4]>,
+    /// `FocalLength`, in mm.
+    pub focal_length: Option<f64>,
+    /// `FNumber`
+    pub f_number: Option<f64>,
+    /// `ExposureTime`, in seconds.
+    pub exposure_time: Option<f64>,
+    /// `PhotographicSensitivity`
+    pub iso: Option<u32>,
+}
+
+/// Rationals of a field; 0/0, which EXIF uses for unknown, is `None`.
+fn rationals(exif: &exif::Exif, tag: Tag) -> Option<Vec<Option<f64>>> {
+    let Value::Rational(ref values) = exif.get_field(tag, In::PRIMARY)?.value else {
+        return None;
+    };
+    Some(values.iter().map(|x| (x.denom != 0).then(|| x.to_f64())).collect())
+}
+
+fn rational(exif: &exif::Exif, tag: Tag) -> Option<f64> {
+    rationals(exif, tag)?.first().copied().flatten()
+}
+
+/// `24`, `2.8`: no trailing zeros.
+fn number(x: f64) -> String {
+    let s = format!("{:.1}", x);
+    s.strip_suffix(".0").map(str::to_string).unwrap_or(s)
+}
+
+impl Exposure {
+    pub fn from_exif(exif: &exif::Exif) -> Exposure {
+        Exposure {
+            lens: exif_ascii(exif, Tag::LensModel),
+            lens_spec: rationals(exif, Tag::LensSpecification)
+                .filter(|x| x.len() == 4 && x.iter().any(Option::is_some))
+                .map(|x| [x[0], x[1], x[2], x[3]]),
+            focal_length: rational(exif, Tag::FocalLength),
+            f_number: rational(exif, Tag::FNumber),
+            exposure_time: rational(exif, Tag::ExposureTime),
+            iso: exif
+                .get_field(Tag::PhotographicSensitivity, In::PRIMARY)
+                .and_then(|x| x.value.get_uint(0)),
+        }
+    }
+
+    /// Keep what is known, take the rest from `other`.
+    pub fn fill(&mut self, other: Exposure) {
+        self.lens = self.lens.take().or(other.lens);
+        self.lens_spec = self.lens_spec.or(other.lens_spec);
+        self.focal_length = self.focal_length.or(other.focal_length);
+        self.f_number = self.f_number.or(other.f_number);
+        self.exposure_time = self.exposure_time.or(other.exposure_time);
+        self.iso = self.iso.or(other.iso);
+    }
+
+    /// `24-70mm F2.8`, `35mm F1.4`, `100-400mm F4.5-5.6`.
+    pub fn lens_spec_text(&self) -> Option<String> {
+        let [short, long, wide, narrow] = self.lens_spec?;
+        let short = short?;
+        let mut s = match long.filter(|x| *x != short) {
+            Some(long) => format!("{}-{}mm", number(short), number(long)),
+            None => format!("{}mm", number(short)),
+        };
+        if let Some(wide) = wide {
+            s.push_str(&format!(" F{}", number(wide)));
+            if let Some(narrow) = narrow.filter(|x| *x != wide) {
+                s.push_str(&format!("-{}", number(narrow)));
+            }
+        }
+        Some(s)
+    }
+
+    /// `24`, in mm.
+    pub fn focal_text(&self) -> Option<String> {
+        self.focal_length.map(number)
+    }
+
+    /// `2.8`
+    pub fn aperture_text(&self) -> Option<String> {
+        self.f_number.map(number)
+    }
+
+    /// `1/250` below a third of a second, else `0.5`, `2`, in seconds.
+    pub fn shutter_text(&self) -> Option<String> {
+        let t = self.exposure_time.filter(|x| *x > 0.0)?;
+        Some(if t < 0.3 {
+            format!("1/{}", (1.0 / t).round())
+        } else {
+            number(t)
+        })
+    }
+
+    pub fn iso_text(&self) -> Option<String> {
+        self.iso.map(|x| x.to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_text() {
+        let exposure = Exposure {
+            lens_spec: Some([Some(100.0), Some(400.0), Some(4.5), Some(5.6)]),
+            focal_length: Some(24.0),
+            f_number: Some(2.8),
+            exposure_time: Some(0.004),
+            iso: Some(100),
+            ..Default::default()
+        };
+        assert_eq!(exposure.lens_spec_text().as_deref(), Some("100-400mm F4.5-5.6"));
+        assert_eq!(exposure.focal_text().as_deref(), Some("24"));
+        assert_eq!(exposure.aperture_text().as_deref(), Some("2.8"));
+        assert_eq!(exposure.shutter_text().as_deref(), Some("1/250"));
+        assert_eq!(exposure.iso_text().as_deref(), Some("100"));
+
+        let prime = Exposure {
+            lens_spec: Some([Some(35.0), Some(35.0), Some(1.4), None]),
+            exposure_time: Some(2.0),
+            ..Default::default()
+        };
+        assert_eq!(prime.lens_spec_text().as_deref(), Some("35mm F1.4"));
+        assert_eq!(prime.shutter_text().as_deref(), Some("2"));
+    }
+}
@@ -1,16 +1,26 @@
 #![allow(dead_code)]
 
-use exif::{In, Tag};
+use chrono::{Duration as TimeDelta, FixedOffset, NaiveDate, NaiveDateTime};
+use exif::{Context, In, Tag, Value};
 use lazy_static::lazy_static;
 use regex::Regex;
 use sha2::Digest;
 use std::path::Path;
 use std::result::Result;
 use std::string::String;
-use std::time::SystemTime;
+use std::time::{Duration, SystemTime};
 use thiserror::Error;
 
+use crate::core::bmff;
+use crate::core::config::{self, Config};
+use crate::core::datesource::{self, DateSource};
+use crate::core::exposure::Exposure;
+use crate::core::makernote;
+use crate::core::utils;
+use crate::core::zone::{self, Zone};
+
 const MAX_NUMBER: u32 = 100000;
+pub const DATETIME_FORMAT: &str = "%Y%m%d_%H%M%S";
 
 static MODEL_MAP: phf::Map<&'static str, &'static str> = phf::phf_map! {
     "iPhone 6s" => "IP6S",
@@ -26,14 +36,99 @@ lazy_static! {
     static ref FILE_NAME_PATTERN_V1: Regex =
         Regex::new(r"(\D)_(\d{5})__(\d{8}_\d{6})").expect("FILE_NAME_PATTERN_V1");
     static ref FILE_NAME_PATTERN_V2: Regex =
-        Regex::new(r"(\d{8}_\d{6})__(\d{2,5})__(.{1,})").unwrap();
+        Regex::new(r"(\d{8}_\d{6})(?:_(\d{3}))?__(\d{2,5})__(.{1,})").unwrap();
     static ref NUMBER_IN_FILE_NAME: Regex = Regex::new(r".+?(\d{2,})").unwrap();
     static ref FILE_WITHOUT_DATE: Regex =
-        Regex::new(r"(\d{6})__(\d{2,5})__(.{1,})").expect("SHORT_FILE_NAME");
+        Regex::new(r"(\d{6})(?:_\d{3})?__(\d{2,5})__(.{1,})").expect("SHORT_FILE_NAME");
     static ref SHORT_FILE_WITH_DIR: Regex =
         Regex::new(r"(\d{8})/(\d{6})__(\d{2,5})__(.{1,})").unwrap();
     static ref COMPACT_FORM_1: Regex =
-        Regex::new(r"(\d{8})/(\d{6})__(\d{3,5})__(.+)\.([^.]+)").expect("COMPACT_FORM_1");
+        Regex::new(r"(\d{8})/(\d{6})(?:_(\d{3}))?__(\d{3,5})__(.+)\.([^.]+)").expect("COMPACT_FORM_1");
+}
+
+/// Keep what is safe in a file name: ASCII letters, digits and dashes.
+fn sanitize_model(raw: &str) -> String {
+    raw.chars()
+        .filter(|c| c.is_ascii_alphanumeric() || *c == '-')
+        .collect::<String>()
+        .to_uppercase()
+}
+
+/// Short model name for an EXIF `Model`: the user's aliases first, then the
+/// built-in map, then the sanitised raw value or `UNSET`.
+fn model_name(config: &Config, raw: &str) -> String {
+    let raw = raw.trim();
+    if let Some(name) = config.models.get(raw) {
+        return name.clone();
+    }
+    if let Some(name) = MODEL_MAP.get(raw) {
+        return name.to_string();
+    }
+    let name = sanitize_model(raw);
+    if config.raw_models && !name.is_empty() {
+        name
+    } else {
+        "UNSET".to_string()
+    }
+}
+
+/// Short name for a body: its alias by serial number if the config has
+/// one, else that of its model.
+fn body_name(config: &Config, raw_model: &str, serial: Option<&str>) -> String {
+    match serial.and_then(|x| config.bodies.get(x)) {
+        Some(name) => name.clone(),
+        None => model_name(config, raw_model),
+    }
+}
+
+/// Shift a capture time by the clock correction configured for `model`.
+fn correct_clock(config: &Config, model: &str, datetime: NaiveDateTime) -> NaiveDateTime {
+    match config.clock.get(model) {
+        Some(seconds) => datetime + TimeDelta::seconds(*seconds),
+        None => datetime,
+    }
+}
+
+/// A `20230105_150108` date and time, `None` unless it is a real one.
+fn parse_datetime(s: &str) -> Option<NaiveDateTime> {
+    NaiveDateTime::parse_from_str(s, DATETIME_FORMAT).ok()
+}
+
+/// DNG `CameraSerialNumber`, in IFD0.
+const TAG_CAMERA_SERIAL: Tag = Tag(Context::Tiff, 0xc62f);
+
+/// Star rating set in camera or by an editor (`Rating`), in IFD0.
+const TAG_RATING: Tag = Tag(Context::Tiff, 0x4746);
+
+fn read_exif(path: &str) -> Result<exif::Exif, InfoErr> {
+    let file = std::fs::File::open(path).map_err(|_| InfoErr::Io("open", path.into()))?;
+    let mut buf_reader = std::io::BufReader::new(&file);
+    exif::Reader::new()
+        .read_from_container(&mut buf_reader)
+        .map_err(|_| InfoErr::Exif("parse", path.into()))
+}
+
+/// A non-empty ASCII field, trimmed.
+pub(crate) fn exif_ascii(exif: &exif::Exif, tag: Tag) -> Option<String> {
+    let field = exif.get_field(tag, In::PRIMARY)?;
+    let Value::Ascii(ref values) = field.value else {
+        return None;
+    };
+    let value = String::from_utf8_lossy(values.first()?).trim().to_string();
+    (!value.is_empty()).then_some(value)
+}
+
+/// `Rating`, 0 to 5 stars.
+fn exif_rating(exif: &exif::Exif) -> Option<u8> {
+    let rating = exif.get_field(TAG_RATING, In::PRIMARY)?.value.get_uint(0)?;
+    u8::try_from(rating).ok().filter(|x| *x <= 5)
+}
+
+/// `BodySerialNumber`, else the DNG or maker note serial.
+fn exif_serial(exif: &exif::Exif) -> Option<String> {
+    exif_ascii(exif, Tag::BodySerialNumber)
+        .or_else(|| exif_ascii(exif, TAG_CAMERA_SERIAL))
+        .or_else(|| makernote::serial(exif))
 }
 
 fn split_path_2(path: &str) -> Option<(&str, &str, &str)> {
@@ -73,6 +168,16 @@ fn number_from_file_name(file_name: &str) -> String {
     number_from_file_name_hash(file_name)
 }
 
+/// Milliseconds from an EXIF `SubSecTime*` value, a decimal fraction of
+/// the second written without the point: "12" is 120 ms.
+pub(crate) fn parse_subsec(value: &str) -> Option<u16> {
+    let digits: String = value.trim().chars().take_while(|c| c.is_ascii_digit()).collect();
+    if digits.is_empty() {
+        return None;
+    }
+    format!("{digits:0<3}")[..3].parse().ok()
+}
+
 fn file_ext_normal(ext: &str) -> String {
     if ext == "jpeg" || ext == "JPEG" {
         "JPG".into()
@@ -83,17 +188,34 @@ fn file_ext_normal(ext: &str) -> String {
 
 pub struct Info {
     pub model: String,
-    pub datetime: String,
+    /// Capture time to the second, as the camera's clock showed it.
+    pub datetime: NaiveDateTime,
+    /// Sub-second part of the capture time, when known.
+    pub millis: Option<u16>,
+    /// Offset of the camera's clock from UTC, when recorded.
+    pub offset: Option<FixedOffset>,
+    pub source: DateSource,
+    pub exposure: Exposure,
+    /// Body serial number, for telling bodies of one model apart.
+    pub serial: Option<String>,
+    /// Stars, 0 to 5, set in camera or by an editor.
+    pub rating: Option<u8>,
+    /// Length of a video, from its movie header.
+    pub duration: Option<Duration>,
     pub number: String,
     pub ext: String,
     pub ver: InfoVer,
 }
 
-#[derive(PartialEq)]
+#[derive(Clone, Copy, Debug, PartialEq)]
 pub enum InfoVer {
+    /// `A_02104__20230105_150108`
     V1,
+    /// `20230105_150108__02104__A7R4A`
     V2,
-    EXIF,
+    /// `20230105/150108__02104__A7R4A`, the date in the directory.
+    Compact,
+    Exif,
 }
 
 #[derive(Error, Debug)]
@@ -136,7 +258,14 @@ impl Info {
         if let Some(captures) = FILE_NAME_PATTERN_V1.captures(file_stem) {
             return Some(Self {
                 model: Self::convert_v1_model_to_v2(captures.get(1)?.as_str()).to_string(),
-                datetime: captures.get(3)?.as_str().to_string(),
+                datetime: parse_datetime(captures.get(3)?.as_str())?,
+                millis: None,
+                offset: None,
+                source: DateSource::Named,
+                exposure: Exposure::default(),
+                serial: None,
+                rating: None,
+                duration: None,
                 number: captures.get(2)?.as_str().to_string(),
                 ext: file_ext_normal(file_ext),
                 ver: InfoVer::V1,
@@ -145,9 +274,16 @@ impl Info {
 
         if let Some(captures) = FILE_NAME_PATTERN_V2.captures(file_stem) {
             return Some(Self {
-                model: captures.get(3)?.as_str().to_string(),
-                datetime: captures.get(1)?.as_str().to_string(),
-                number: captures.get(2)?.as_str().to_string(),
+                model: captures.get(4)?.as_str().to_string(),
+                datetime: parse_datetime(captures.get(1)?.as_str())?,
+                millis: captures.get(2).and_then(|x| x.as_str().parse().ok()),
+                offset: None,
+                source: DateSource::Named,
+                exposure: Exposure::default(),
+                serial: None,
+                rating: None,
+                duration: None,
+                number: captures.get(3)?.as_str().to_string(),
                 ext: file_ext_normal(file_ext),
                 ver: InfoVer::V2,
             });
@@ -155,66 +291,118 @@ impl Info {
 
         if let Some(captures) = COMPACT_FORM_1.captures(path) {
             return Some(Self {
-                model: captures.get(4)?.as_str().to_string(),
-                datetime: format!(
+                model: captures.get(5)?.as_str().to_string(),
+                datetime: parse_datetime(&format!(
                     "{}_{}",
                     captures.get(1)?.as_str(),
                     captures.get(2)?.as_str()
-                ),
-                number: captures.get(3)?.as_str().to_string(),
-                ext: file_ext_normal(captures.get(5)?.as_str()),
-                ver: InfoVer::V2,
+                ))?,
+                millis: captures.get(3).and_then(|x| x.as_str().parse().ok()),
+                offset: None,
+                source: DateSource::Named,
+                exposure: Exposure::default(),
+                serial: None,
+                rating: None,
+                duration: None,
+                number: captures.get(4)?.as_str().to_string(),
+                ext: file_ext_normal(captures.get(6)?.as_str()),
+                ver: InfoVer::Compact,
             });
         }
         None
     }
 
+    /// Read EXIF and apply the configured clock correction of the body to
+    /// times taken from its clock.
     pub fn from_exif(path: &str) -> Result<Self, InfoErr> {
+        let info = Self::from_exif_raw(path)?;
+        if !info.source.is_camera_clock() {
+            return Ok(info);
+        }
+        let datetime = correct_clock(config::get(), &info.model, info.datetime);
+        Ok(Self { datetime, ..info })
+    }
+
+    /// Read EXIF as the camera recorded it, without clock correction.
+    /// Videos are read from their movie header instead. Without a
+    /// `DateTimeOriginal` the time comes from the fallbacks in
+    /// `DateSource`, and without a `Model` the model is `UNSET`.
+    pub fn from_exif_raw(path: &str) -> Result<Self, InfoErr> {
         type E = InfoErr;
         let (_dir, file_stem, file_ext) =
             split_path_2(path).ok_or_else(|| E::Io("split", path.into()))?;
 
         let number = number_from_file_name(file_stem);
+        if utils::is_video_ext(file_ext.to_lowercase()) {
+            return Self::from_movie(path, number, file_ext);
+        }
 
-        let file = std::fs::File::open(path).map_err(|_| E::Io("open", path.into()))?;
-
-        let mut buf_reader = std::io::BufReader::new(&file);
-        let exif_reader = exif::Reader::new();
-        let exif = exif_reader
-            .read_from_container(&mut buf_reader)
-            .map_err(|_| E::Exif("parse", path.into()))?;
-
-        let model_field = exif
-            .get_field(Tag::Model, In::PRIMARY)
-            .ok_or_else(|| E::Exif("model", path.into()))?;
-        let datetime_field = exif
-            .get_field(Tag::DateTimeOriginal, In::PRIMARY)
+        let exif = read_exif(path).ok();
+        let found = datesource::find(Path::new(path), exif.as_ref())
             .ok_or_else(|| E::Exif("datetime", path.into()))?;
+        let ascii = |tag| exif.as_ref().and_then(|x| exif_ascii(x, tag));
 
-        let model_value = model_field
-            .display_value()
-            .to_string()
-            .clone()
-            .replace("\"", "");
-        let datetime = datetime_field
-            .display_value()
-            .to_string()
-            .clone()
-            .replace(" ", "_")
-            .replace(":", "")
-            .replace("-", "");
-
-        let model = MODEL_MAP.get(&model_value).unwrap_or(&"UNSET").to_string();
-        // println!("model:{}, datetime:{}", model_value, datetime_value);
+        let serial = exif.as_ref().and_then(exif_serial);
+        let model = body_name(config::get(), &ascii(Tag::Model).unwrap_or_default(), serial.as_deref());
         Ok(Self {
             model,
-            datetime,
+            datetime: found.datetime,
+            millis: found.millis,
+            offset: found.offset,
+            source: found.source,
+            exposure: exif.as_ref().map(Exposure::from_exif).unwrap_or_default(),
+            serial,
+            rating: exif.as_ref().and_then(exif_rating),
+            duration: None,
             number,
             ext: file_ext_normal(file_ext),
-            ver: InfoVer::EXIF,
+            ver: InfoVer::Exif,
         })
     }
 
+    /// An MP4/MOV: the vendor creation time with its offset if there is
+    /// one, else the UTC `mvhd` time in the `--tz` zone, else the
+    /// fallbacks that need no EXIF.
+    fn from_movie(path: &str, number: String, file_ext: &str) -> Result<Self, InfoErr> {
+        type E = InfoErr;
+        let movie = bmff::read(path).map_err(|_| E::Exif("movie", path.into()))?;
+        let found = movie
+            .local
+            .or_else(|| movie.created.map(|x| zone::get().wall_clock(x)))
+            .map(|x| datesource::Found::zoned(x, DateSource::Movie))
+            .or_else(|| datesource::find_outside(Path::new(path)))
+            .ok_or_else(|| E::Exif("datetime", path.into()))?;
+        Ok(Self {
+            model: model_name(config::get(), movie.model.as_deref().unwrap_or_default()),
+            datetime: found.datetime,
+            millis: found.millis,
+            offset: found.offset,
+            source: found.source,
+            exposure: Exposure::default(),
+            serial: None,
+            rating: None,
+            duration: movie.duration,
+            number,
+            ext: file_ext_normal(file_ext),
+            ver: InfoVer::Exif,
+        })
+    }
+
+    /// Fill exposure, serial and rating from EXIF for an `Info` read from the file
+    /// name. Files without EXIF are left as they are.
+    pub fn fill_from_exif(&mut self, path: &str) {
+        let Ok(exif) = read_exif(path) else {
+            return;
+        };
+        self.exposure.fill(Exposure::from_exif(&exif));
+        if self.serial.is_none() {
+            self.serial = exif_serial(&exif);
+        }
+        if self.rating.is_none() {
+            self.rating = exif_rating(&exif);
+        }
+    }
+
     pub fn from_exif_2(path: &str, number: &str) -> Result<Self, InfoErr> {
         Self::from_exif(path).map(|e| Self {
             number: number.to_string(),
@@ -222,23 +410,35 @@ impl Info {
         })
     }
 
+    /// `_mmm` after the time when names carry milliseconds and they are known.
+    fn millis_suffix(&self) -> String {
+        match self.millis {
+            Some(ms) if config::get().millis => format!("_{ms:03}"),
+            _ => String::new(),
+        }
+    }
+
     pub fn to_name(&self) -> String {
-        format!("{}__{}__{}", self.datetime, self.number, self.model)
+        format!("{}{}__{}__{}", self.to_datetime(), self.millis_suffix(), self.number, self.model)
     }
 
     pub fn to_compact_name(&self) -> String {
-        format!("{}__{}__{}", &self.datetime[9..15], self.number, self.model)
+        format!("{}{}__{}__{}", self.to_time(), self.millis_suffix(), self.number, self.model)
     }
 
     pub fn to_file_name(&self) -> String {
         format!("{}.{}", self.to_name(), self.ext)
     }
 
+    /// Replace what a V1 name says with what the file records. A time
+    /// guessed from outside the file does not replace the name's.
     pub fn update_from_exif(self, path: &str) -> Self {
-        if self.ver == InfoVer::V2 {
+        if matches!(self.ver, InfoVer::V2 | InfoVer::Compact) {
             self
         } else {
             Self::from_exif(path)
+                .ok()
+                .filter(|m| m.source.is_embedded())
                 .map(|m| Self {
                     number: self.number.clone(),
                     ..m
@@ -247,38 +447,42 @@ impl Info {
         }
     }
 
-    pub fn to_dir_and_full(&self, dir: &str) -> (String, String) {
-        let date_str = &self.datetime[0..8];
-        let dest_dir = format!("{dir}/{date_str}");
-        let dest_path = format!("{dest_dir}/{}.{}", self.to_name(), self.ext);
-        (dest_dir, dest_path)
+    /// Capture time including the sub-second part.
+    pub fn timestamp(&self) -> NaiveDateTime {
+        self.datetime + TimeDelta::milliseconds(self.millis.unwrap_or(0).into())
+    }
+
+    /// Capture time as an instant, at the offset the camera recorded or
+    /// else in `zone`. `None` when the time does not exist in `zone`.
+    pub fn to_systemtime(&self, zone: &Zone) -> Option<SystemTime> {
+        let zone = self.offset.map(Zone::Fixed).unwrap_or(*zone);
+        zone.instant(self.timestamp()).map(SystemTime::from)
     }
 
-    pub fn to_compact_dir_and_full(&self, dir: &str) -> (String, String) {
-        let date_str = &self.datetime[0..8];
-        let dest_dir = format!("{dir}/{date_str}");
-        let dest_path = format!("{dest_dir}/{}.{}", self.to_compact_name(), self.ext);
-        (dest_dir, dest_path)
+    pub fn date(&self) -> NaiveDate {
+        self.datetime.date()
     }
 
-    pub fn to_systemtime(&self) -> SystemTime {
-        let date = chrono::NaiveDateTime::parse_from_str(&self.datetime, "%Y%m%d_%H%M%S").unwrap();
-        let date = date.and_local_timezone(chrono::offset::Local).unwrap();
-        SystemTime::from(date)
+    /// `20230105_150108`
+    pub fn to_datetime(&self) -> String {
+        self.datetime.format(DATETIME_FORMAT).to_string()
     }
 
+    /// `20230105`
     pub fn to_date(&self) -> String {
-        self.datetime[0..8].to_string()
+        self.datetime.format("%Y%m%d").to_string()
     }
 
+    /// `150108`
     pub fn to_time(&self) -> String {
-        self.datetime[9..15].to_string()
+        self.datetime.format("%H%M%S").to_string()
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use super::number_from_file_name;
+    use super::{body_name, correct_clock, model_name, number_from_file_name, parse_datetime, parse_subsec, Info};
+    use crate::core::config::Config;
     use regex::Regex;
     use sha2::Digest;
     use std::ffi::OsStr;
@@ -296,6 +500,52 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_model_name() {
+        let mut config = Config::default();
+        assert_eq!(model_name(&config, "ILCE-1"), "A1");
+        assert_eq!(model_name(&config, "NIKON Z 6_2"), "UNSET");
+
+        config.raw_models = true;
+        config.models.insert("ILCE-1".to_string(), "A1X".to_string());
+        assert_eq!(model_name(&config, "ILCE-1"), "A1X");
+        assert_eq!(model_name(&config, "NIKON Z 6_2 "), "NIKONZ62");
+        assert_eq!(model_name(&config, " "), "UNSET");
+
+        config.bodies.insert("5012345".to_string(), "A1a".to_string());
+        assert_eq!(body_name(&config, "ILCE-1", Some("5012345")), "A1a");
+        assert_eq!(body_name(&config, "ILCE-1", Some("5067890")), "A1X");
+        assert_eq!(body_name(&config, "ILCE-1", None), "A1X");
+    }
+
+    #[test]
+    fn test_correct_clock() {
+        let mut config = Config::default();
+        config.clock.insert("A7R4A".to_string(), -192);
+        let t = parse_datetime("20230105_000100").unwrap();
+        let f = |x: chrono::NaiveDateTime| x.format(super::DATETIME_FORMAT).to_string();
+        assert_eq!(f(correct_clock(&config, "A7R4A", t)), "20230104_235748");
+        assert_eq!(f(correct_clock(&config, "A1", t)), "20230105_000100");
+        assert!(parse_datetime("20231399_000100").is_none());
+        assert!(Info::from_path("20231399_150108__03212__A1.ARW").is_none());
+    }
+
+    #[test]
+    fn test_millis() {
+        assert_eq!(parse_subsec("12"), Some(120));
+        assert_eq!(parse_subsec("0345 "), Some(34));
+        assert_eq!(parse_subsec(""), None);
+
+        let info = Info::from_path("20230105_150108_120__03212__A1.ARW").unwrap();
+        assert_eq!(info.to_datetime(), "20230105_150108");
+        assert_eq!((info.millis, info.number.as_str(), info.model.as_str()), (Some(120), "03212", "A1"));
+        let info = Info::from_path("20230105_150108__03212__A1.ARW").unwrap();
+        assert_eq!((info.millis, info.model.as_str()), (None, "A1"));
+        let info = Info::from_path("x/20230105/150108_007__03212__A1.ARW").unwrap();
+        assert_eq!((info.to_datetime().as_str(), info.millis), ("20230105_150108", Some(7)));
+        assert_eq!(info.timestamp().format("%H:%M:%S%.3f").to_string(), "15:01:08.007");
+    }
+
     #[test]
     fn test_hash_sha2() {
         let mut hasher = sha2::Sha256::new();
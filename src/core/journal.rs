@@ -0,0 +1,179 @@
+use std::collections::HashMap;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+pub const JOURNAL_FILE: &str = ".iphoto-import.journal";
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum State {
+    Pending,
+    Copying,
+    Verified,
+}
+
+impl State {
+    fn as_str(&self) -> &'static str {
+        match self {
+            State::Pending => "pending",
+            State::Copying => "copying",
+            State::Verified => "verified",
+        }
+    }
+
+    fn parse(s: &str) -> Option<State> {
+        match s {
+            "pending" => Some(State::Pending),
+            "copying" => Some(State::Copying),
+            "verified" => Some(State::Verified),
+            _ => None,
+        }
+    }
+}
+
+/// Append-only record of per-file import state, kept in the destination
+/// root. Each line is `state \t dest \t src [\t sha256]`; the last line for
+/// a destination wins. The file is only created by the first `mark`.
+pub struct Journal {
+    path: PathBuf,
+    file: Option<File>,
+    /// State and source of each destination.
+    entries: HashMap<String, (State, String)>,
+}
+
+impl Journal {
+    pub fn open<P: AsRef<Path>>(root: P) -> io::Result<Journal> {
+        let path = root.as_ref().join(JOURNAL_FILE);
+        let entries = match fs::read_to_string(&path) {
+            Ok(text) => parse(&text),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => HashMap::new(),
+            Err(e) => return Err(e),
+        };
+        Ok(Journal {
+            path,
+            file: None,
+            entries,
+        })
+    }
+
+    pub fn state(&self, dest: &str) -> Option<State> {
+        self.entries.get(dest).map(|x| x.0)
+    }
+
+    pub fn count(&self, state: State) -> usize {
+        self.entries.values().filter(|x| x.0 == state).count()
+    }
+
+    /// Destinations that were being written when the previous run stopped.
+    pub fn partials(&self) -> Vec<String> {
+        self.entries
+            .iter()
+            .filter(|(_, x)| x.0 == State::Copying)
+            .map(|(d, _)| d.clone())
+            .collect()
+    }
+
+    pub fn mark(&mut self, state: State, dest: &str, src: &str, sum: Option<&str>) -> io::Result<()> {
+        let line = match sum {
+            Some(sum) => format!("{}\t{dest}\t{src}\t{sum}\n", state.as_str()),
+            None => format!("{}\t{dest}\t{src}\n", state.as_str()),
+        };
+        let file = match &mut self.file {
+            Some(file) => file,
+            None => {
+                if let Some(root) = self.path.parent() {
+                    fs::create_dir_all(root)?;
+                }
+                let file = OpenOptions::new().create(true).append(true).open(&self.path)?;
+                self.file.insert(file)
+            }
+        };
+        file.write_all(line.as_bytes())?;
+        file.flush()?;
+        self.entries.insert(dest.to_string(), (state, src.to_string()));
+        Ok(())
+    }
+
+    /// Remove the destinations under `root` that were being written when
+    /// the previous run stopped, and mark them pending. A source is only
+    /// deleted after its copy is verified, so a missing source means the
+    /// card moved or was left out, not that the copy is whole. Returns how
+    /// many files were removed.
+    pub fn recover(&mut self, root: &Path) -> io::Result<usize> {
+        let mut removed = 0;
+        for key in self.partials() {
+            let src = self.entries[&key].1.clone();
+            let dest = root.join(&key);
+            if dest.is_file() {
+                fs::remove_file(&dest)?;
+                removed += 1;
+            }
+            self.mark(State::Pending, &key, &src, None)?;
+        }
+        Ok(removed)
+    }
+
+    /// Remove the journal once an import has run to completion.
+    pub fn finish(self) -> io::Result<()> {
+        drop(self.file);
+        match fs::remove_file(&self.path) {
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(()),
+            r => r,
+        }
+    }
+}
+
+fn parse(text: &str) -> HashMap<String, (State, String)> {
+    let mut entries = HashMap::new();
+    for line in text.lines() {
+        let mut fields = line.split('\t');
+        let state = fields.next().and_then(State::parse);
+        let dest = fields.next();
+        let src = fields.next().unwrap_or_default();
+        if let (Some(state), Some(dest)) = (state, dest) {
+            entries.insert(dest.to_string(), (state, src.to_string()));
+        }
+    }
+    entries
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_last_state_wins() {
+        let text = "pending\t20230105/a.ARW\t/card/a.ARW\n\
+                    pending\t20230105/b.ARW\t/card/b.ARW\n\
+                    copying\t20230105/a.ARW\t/card/a.ARW\n\
+                    verified\t20230105/a.ARW\t/card/a.ARW\tabcd\n\
+                    copying\t20230105/b.ARW\t/card/b.ARW\n\
+                    garbage line\n";
+        let entries = parse(text);
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries["20230105/a.ARW"].0, State::Verified);
+        assert_eq!(entries["20230105/b.ARW"], (State::Copying, "/card/b.ARW".to_string()));
+    }
+
+    #[test]
+    fn test_recover_removes_partials() {
+        let root = std::env::temp_dir().join(format!("iphoto-journal-{}", std::process::id()));
+        fs::create_dir_all(root.join("20230105")).unwrap();
+
+        // a was verified; b was being copied from a card since pulled
+        let mut journal = Journal::open(&root).unwrap();
+        journal.mark(State::Copying, "20230105/a.ARW", "/card/a.ARW", None).unwrap();
+        fs::write(root.join("20230105/a.ARW"), "a.ARW").unwrap();
+        journal.mark(State::Verified, "20230105/a.ARW", "/card/a.ARW", Some("abcd")).unwrap();
+        journal.mark(State::Copying, "20230105/b.ARW", "/card/b.ARW", None).unwrap();
+        fs::write(root.join("20230105/b.ARW"), "b.A").unwrap();
+        drop(journal);
+
+        let mut journal = Journal::open(&root).unwrap();
+        assert_eq!(journal.recover(&root).unwrap(), 1);
+        assert!(root.join("20230105/a.ARW").is_file());
+        assert!(!root.join("20230105/b.ARW").exists());
+        assert_eq!(journal.state("20230105/b.ARW"), Some(State::Pending));
+        fs::remove_dir_all(&root).unwrap();
+    }
+}
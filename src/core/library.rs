@@ -0,0 +1,156 @@
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use filetime::FileTime;
+use walkdir::WalkDir;
+
+pub const INDEX_FILE: &str = ".iphoto-index";
+
+struct Entry {
+    size: u64,
+    mtime: i64,
+    sum: Option<String>,
+}
+
+/// Content index of a destination library. Sizes and mtimes are refreshed
+/// from disk on open; digests are computed lazily, only for files whose
+/// size matches something being looked up, and cached in `.iphoto-index`.
+pub struct Library {
+    root: PathBuf,
+    entries: HashMap<String, Entry>,
+}
+
+fn is_hidden(name: &str) -> bool {
+    name.starts_with('.')
+}
+
+fn mtime_of(meta: &fs::Metadata) -> i64 {
+    FileTime::from_last_modification_time(meta).unix_seconds()
+}
+
+impl Library {
+    pub fn open<P: AsRef<Path>>(root: P) -> io::Result<Library> {
+        let root = root.as_ref().to_path_buf();
+        let mut cached = match fs::read_to_string(root.join(INDEX_FILE)) {
+            Ok(text) => parse(&text),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => HashMap::new(),
+            Err(e) => return Err(e),
+        };
+
+        let mut entries = HashMap::new();
+        let walker = WalkDir::new(&root)
+            .min_depth(1)
+            .into_iter()
+            .filter_entry(|e| !is_hidden(&e.file_name().to_string_lossy()));
+        for e in walker.flatten() {
+            if !e.file_type().is_file() {
+                continue;
+            }
+            let Ok(rel) = e.path().strip_prefix(&root) else {
+                continue;
+            };
+            let rel = rel.to_string_lossy().to_string();
+            let meta = e.metadata().map_err(io::Error::other)?;
+            let (size, mtime) = (meta.len(), mtime_of(&meta));
+            let sum = cached
+                .remove(&rel)
+                .filter(|c| c.size == size && c.mtime == mtime)
+                .and_then(|c| c.sum);
+            entries.insert(rel, Entry { size, mtime, sum });
+        }
+        Ok(Library { root, entries })
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Files of the given size, with their digest when already known.
+    pub fn candidates(&self, size: u64) -> Vec<(String, Option<String>)> {
+        self.entries
+            .iter()
+            .filter(|(_, e)| e.size == size)
+            .map(|(rel, e)| (rel.clone(), e.sum.clone()))
+            .collect()
+    }
+
+    pub fn path(&self, rel: &str) -> PathBuf {
+        self.root.join(rel)
+    }
+
+    /// Cached digest of a library file, if it has been hashed before.
+    pub fn cached_sum(&self, rel: &str) -> Option<String> {
+        self.entries.get(rel).and_then(|e| e.sum.clone())
+    }
+
+    pub fn set_sum(&mut self, rel: &str, sum: String) {
+        if let Some(entry) = self.entries.get_mut(rel) {
+            entry.sum = Some(sum);
+        }
+    }
+
+    pub fn insert(&mut self, rel: &str, sum: String) -> io::Result<()> {
+        let meta = fs::metadata(self.root.join(rel))?;
+        self.entries.insert(
+            rel.to_string(),
+            Entry {
+                size: meta.len(),
+                mtime: mtime_of(&meta),
+                sum: Some(sum),
+            },
+        );
+        Ok(())
+    }
+
+    /// Follow a file renamed inside the library, keeping its digest.
+    pub fn rename(&mut self, from: &str, to: &str) {
+        if let Some(entry) = self.entries.remove(from) {
+            self.entries.insert(to.to_string(), entry);
+        }
+    }
+
+    pub fn save(&self) -> io::Result<()> {
+        let mut text = String::new();
+        for (rel, e) in &self.entries {
+            let sum = e.sum.as_deref().unwrap_or("-");
+            text.push_str(&format!("{}\t{}\t{}\t{}\n", e.size, e.mtime, sum, rel));
+        }
+        fs::create_dir_all(&self.root)?;
+        fs::write(self.root.join(INDEX_FILE), text)
+    }
+}
+
+fn parse(text: &str) -> HashMap<String, Entry> {
+    let mut entries = HashMap::new();
+    for line in text.lines() {
+        let fields: Vec<&str> = line.splitn(4, '\t').collect();
+        let [size, mtime, sum, rel] = fields[..] else {
+            continue;
+        };
+        let (Ok(size), Ok(mtime)) = (size.parse(), mtime.parse()) else {
+            continue;
+        };
+        let sum = (sum != "-").then(|| sum.to_string());
+        entries.insert(rel.to_string(), Entry { size, mtime, sum });
+    }
+    entries
+}
+
+#[cfg(test)]
+mod tests {
+    use super::parse;
+
+    #[test]
+    fn test_parse_index() {
+        let text = "300000\t1672930868\t-\t20230105/20230105_150108__03212__A1.ARW\n\
+                    1200\t1672930900\tabcd\t20230105/with\ttab.JPG\n\
+                    broken\n";
+        let entries = parse(text);
+        assert_eq!(entries.len(), 2);
+        let e = &entries["20230105/20230105_150108__03212__A1.ARW"];
+        assert_eq!((e.size, e.mtime, e.sum.as_deref()), (300000, 1672930868, None));
+        assert_eq!(entries["20230105/with\ttab.JPG"].sum.as_deref(), Some("abcd"));
+    }
+}
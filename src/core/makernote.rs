@@ -0,0 +1,123 @@
+use exif::{In, Tag, Value};
+
+use crate::core::fninfo::exif_ascii;
+
+/// Nikon `SerialNumber`, ASCII.
+const NIKON_SERIAL: u16 = 0x001d;
+/// Canon `SerialNumber`, LONG.
+const CANON_SERIAL: u16 = 0x000c;
+
+/// An IFD inside a TIFF-style buffer, offsets relative to `buf`.
+struct Ifd<'a> {
+    buf: &'a [u8],
+    le: bool,
+    at: usize,
+}
+
+impl Ifd<'_> {
+    fn u16(&self, at: usize) -> Option<u16> {
+        let b: [u8; 2] = self.buf.get(at..at + 2)?.try_into().ok()?;
+        Some(if self.le { u16::from_le_bytes(b) } else { u16::from_be_bytes(b) })
+    }
+
+    fn u32(&self, at: usize) -> Option<u32> {
+        let b: [u8; 4] = self.buf.get(at..at + 4)?.try_into().ok()?;
+        Some(if self.le { u32::from_le_bytes(b) } else { u32::from_be_bytes(b) })
+    }
+
+    /// Type and raw value of `tag`.
+    fn get(&self, tag: u16) -> Option<(u16, &[u8])> {
+        let count = self.u16(self.at)? as usize;
+        (0..count).find_map(|i| {
+            let entry = self.at + 2 + 12 * i;
+            if self.u16(entry)? != tag {
+                return None;
+            }
+            let kind = self.u16(entry + 2)?;
+            let unit = match kind {
+                3 | 8 => 2,
+                4 | 9 | 11 => 4,
+                5 | 10 | 12 => 8,
+                _ => 1,
+            };
+            let size = unit * self.u32(entry + 4)? as usize;
+            let at = match size {
+                0..=4 => entry + 8,
+                _ => self.u32(entry + 8)? as usize,
+            };
+            Some((kind, self.buf.get(at..at + size)?))
+        })
+    }
+
+    fn long(&self, tag: u16) -> Option<u32> {
+        let (4, value) = self.get(tag)? else {
+            return None;
+        };
+        Ifd { buf: value, le: self.le, at: 0 }.u32(0)
+    }
+
+    fn ascii(&self, tag: u16) -> Option<String> {
+        let (2, value) = self.get(tag)? else {
+            return None;
+        };
+        let value = String::from_utf8_lossy(value).trim_matches(char::from(0)).trim().to_string();
+        (!value.is_empty()).then_some(value)
+    }
+}
+
+/// Nikon type 3 notes: `Nikon\0`, a version, then a TIFF header of their own.
+fn nikon(note: &[u8]) -> Option<String> {
+    let tiff = note.get(10..)?;
+    let le = match tiff.get(..2)? {
+        b"II" => true,
+        b"MM" => false,
+        _ => return None,
+    };
+    let mut ifd = Ifd { buf: tiff, le, at: 0 };
+    ifd.at = ifd.u32(4)? as usize;
+    ifd.ascii(NIKON_SERIAL)
+}
+
+/// Canon notes are a bare IFD whose offsets count from the EXIF TIFF header.
+fn canon(exif: &exif::Exif, at: u32) -> Option<String> {
+    let ifd = Ifd {
+        buf: exif.buf(),
+        le: exif.little_endian(),
+        at: at as usize,
+    };
+    let serial = ifd.long(CANON_SERIAL)?;
+    (serial != 0).then(|| serial.to_string())
+}
+
+/// Body serial number from the maker note, for the makes that keep it
+/// there unenciphered: Nikon and Canon.
+pub fn serial(exif: &exif::Exif) -> Option<String> {
+    let Value::Undefined(ref note, at) = exif.get_field(Tag::MakerNote, In::PRIMARY)?.value else {
+        return None;
+    };
+    if note.starts_with(b"Nikon\0") {
+        return nikon(note);
+    }
+    match exif_ascii(exif, Tag::Make) {
+        Some(make) if make.starts_with("Canon") => canon(exif, at),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_nikon() {
+        // II header, IFD at 8 with one ASCII entry whose value is at 26
+        let mut tiff = b"II*\0\x08\0\0\0".to_vec();
+        tiff.extend([1, 0]);
+        tiff.extend([0x1d, 0, 2, 0, 8, 0, 0, 0, 26, 0, 0, 0]);
+        tiff.extend([0, 0, 0, 0]);
+        tiff.extend(b"3001234\0");
+        let note = [&b"Nikon\0\x02\x11\0\0"[..], &tiff].concat();
+        assert_eq!(nikon(&note).as_deref(), Some("3001234"));
+        assert_eq!(nikon(b"Nikon\0\x02\x11\0\0XX"), None);
+    }
+}
@@ -1,6 +1,18 @@
+pub mod bmff;
+pub mod cardstate;
+pub mod checksum;
+pub mod config;
+pub mod datesource;
+pub mod exposure;
 pub mod fninfo;
+pub mod journal;
 pub mod labelinfo;
+pub mod library;
+pub mod makernote;
+pub mod mounts;
 pub mod scandir;
+pub mod template;
 pub mod touch;
 pub mod utils;
+pub mod zone;
 pub mod progress;
@@ -0,0 +1,169 @@
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+pub const PROC_MOUNTS: &str = "/proc/mounts";
+
+/// Where desktop automounters put removable media.
+pub const MEDIA_ROOTS: [&str; 2] = ["/media", "/run/media"];
+
+/// Block devices by name, partitions included.
+pub const SYS_BLOCK: &str = "/sys/class/block";
+
+/// Filesystems cameras format their cards with.
+const CARD_FSTYPES: [&str; 3] = ["vfat", "exfat", "msdos"];
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Mount {
+    pub device: String,
+    pub point: PathBuf,
+    pub fstype: String,
+}
+
+/// Undo the octal escapes (`\040` for space, ...) used in `/proc/mounts`.
+fn unescape(field: &str) -> String {
+    let bytes = field.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'\\' && i + 4 <= bytes.len() {
+            let digits = std::str::from_utf8(&bytes[i + 1..i + 4]).unwrap_or("");
+            if let Ok(c) = u8::from_str_radix(digits, 8) {
+                out.push(c);
+                i += 4;
+                continue;
+            }
+        }
+        out.push(bytes[i]);
+        i += 1;
+    }
+    String::from_utf8_lossy(&out).to_string()
+}
+
+pub fn parse(text: &str) -> Vec<Mount> {
+    text.lines()
+        .filter_map(|line| {
+            let mut fields = line.split_whitespace();
+            Some(Mount {
+                device: unescape(fields.next()?),
+                point: PathBuf::from(unescape(fields.next()?)),
+                fstype: fields.next()?.to_string(),
+            })
+        })
+        .collect()
+}
+
+pub fn read<P: AsRef<Path>>(path: P) -> io::Result<Vec<Mount>> {
+    Ok(parse(&fs::read_to_string(path)?))
+}
+
+/// Whether the disk holding `device` is removable, per `sys` (a
+/// [`SYS_BLOCK`] layout); `None` when it has no entry there. SD slots wired
+/// to the host report 0, so `mmcblk` disks count as removable too.
+fn removable(sys: &Path, device: &str) -> Option<bool> {
+    let name = Path::new(device).file_name()?;
+    let mut disk = fs::canonicalize(sys.join(name)).ok()?;
+    if disk.join("partition").is_file() {
+        disk.pop();
+    }
+    let flag = fs::read_to_string(disk.join("removable")).ok()?;
+    let mmc = disk.file_name()?.to_string_lossy().starts_with("mmcblk");
+    Some(flag.trim() == "1" || mmc)
+}
+
+/// Mounts below one of `roots` that look like a camera card: a DCIM folder
+/// on a FAT or exFAT volume whose disk, if `sys` knows it, is removable.
+/// This keeps a backup drive that happens to hold a DCIM copy out.
+pub fn cards(mounts: &[Mount], roots: &[&str], sys: &Path) -> Vec<Mount> {
+    mounts
+        .iter()
+        .filter(|m| roots.iter().any(|r| m.point.starts_with(r)))
+        .filter(|m| CARD_FSTYPES.contains(&m.fstype.as_str()))
+        .filter(|m| removable(sys, &m.device).unwrap_or(true))
+        .filter(|m| m.point.join("DCIM").is_dir())
+        .cloned()
+        .collect()
+}
+
+/// The mount holding `path`, i.e. the one with the longest matching point.
+pub fn mount_of<'a>(mounts: &'a [Mount], path: &Path) -> Option<&'a Mount> {
+    mounts
+        .iter()
+        .filter(|m| path.starts_with(&m.point))
+        .max_by_key(|m| m.point.as_os_str().len())
+}
+
+/// Filesystem UUID of a block device, looked up in `/dev/disk/by-uuid`.
+pub fn volume_uuid(device: &str) -> Option<String> {
+    let device = fs::canonicalize(device).ok()?;
+    fs::read_dir("/dev/disk/by-uuid")
+        .ok()?
+        .flatten()
+        .find(|e| fs::canonicalize(e.path()).ok().as_ref() == Some(&device))
+        .map(|e| e.file_name().to_string_lossy().to_string())
+}
+
+/// Volumes directly under `dir` (`/Volumes` on macOS) with a DCIM folder.
+pub fn volume_cards<P: AsRef<Path>>(dir: P) -> Vec<PathBuf> {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return Vec::new();
+    };
+    let mut cards: Vec<PathBuf> = entries
+        .flatten()
+        .map(|e| e.path())
+        .filter(|p| p.join("DCIM").is_dir())
+        .collect();
+    cards.sort();
+    cards
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A sysfs-like tree: `sys/<part>` links to `devices/<disk>/<part>`.
+    fn disk(root: &Path, disk: &str, part: &str, removable: bool) {
+        let dir = root.join("devices").join(disk);
+        fs::create_dir_all(dir.join(part)).unwrap();
+        fs::write(dir.join(part).join("partition"), "1\n").unwrap();
+        fs::write(dir.join("removable"), if removable { "1\n" } else { "0\n" }).unwrap();
+        std::os::unix::fs::symlink(dir.join(part), root.join("sys").join(part)).unwrap();
+    }
+
+    #[test]
+    fn test_parse_and_filter() {
+        let root = std::env::temp_dir().join(format!("iphoto-mounts-{}", std::process::id()));
+        let card = root.join("media/admin/SD CARD");
+        for name in ["SD CARD", "SLOT", "USB", "BACKUP", "EXT"] {
+            fs::create_dir_all(root.join("media/admin").join(name).join("DCIM")).unwrap();
+        }
+        fs::create_dir_all(root.join("media/admin/STICK")).unwrap();
+        fs::create_dir_all(root.join("sys")).unwrap();
+        disk(&root, "sdb", "sdb1", true);
+        disk(&root, "mmcblk0", "mmcblk0p1", false);
+        disk(&root, "sdc", "sdc1", true);
+        disk(&root, "sdd", "sdd1", false);
+        disk(&root, "sde", "sde1", true);
+
+        let root_str = root.to_str().unwrap();
+        let text = format!(
+            "/dev/nvme0n1p2 / ext4 rw,relatime 0 0\n\
+             /dev/sdb1 {root_str}/media/admin/SD\\040CARD exfat rw 0 0\n\
+             /dev/mmcblk0p1 {root_str}/media/admin/SLOT vfat rw 0 0\n\
+             /dev/sdc1 {root_str}/media/admin/STICK vfat rw 0 0\n\
+             /dev/sdd1 {root_str}/media/admin/BACKUP exfat rw 0 0\n\
+             /dev/sde1 {root_str}/media/admin/EXT ext4 rw 0 0\n"
+        );
+        let mounts = parse(&text);
+        assert_eq!(mounts.len(), 6);
+        assert_eq!(mounts[1].point, card);
+        assert_eq!(mounts[1].fstype, "exfat");
+
+        // no DCIM on the stick, the backup disk is fixed, EXT is not FAT
+        let media = format!("{root_str}/media");
+        let found = cards(&mounts, &[media.as_str()], &root.join("sys"));
+        let found: Vec<&str> = found.iter().map(|m| m.device.as_str()).collect();
+        assert_eq!(found, ["/dev/sdb1", "/dev/mmcblk0p1"]);
+        fs::remove_dir_all(&root).unwrap();
+    }
+}
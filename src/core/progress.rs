@@ -1,17 +1,29 @@
 pub struct Progress {
     pub total: usize,
     pub cur: usize,
+    pub total_bytes: u64,
+    pub bytes: u64,
 }
 
 impl Progress {
     pub fn new(total:usize) -> Progress {
         Progress {
-            total: total,
-            cur: 0
+            total,
+            cur: 0,
+            total_bytes: 0,
+            bytes: 0,
         }
     }
 
-    pub fn inc(&mut self)  {
+    pub fn with_bytes(total:usize, total_bytes:u64) -> Progress {
+        Progress {
+            total_bytes,
+            ..Progress::new(total)
+        }
+    }
+
+    pub fn add(&mut self, bytes:u64) {
         self.cur += 1;
+        self.bytes += bytes;
     }
-}
\ No newline at end of file
+}
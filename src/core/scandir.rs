@@ -12,20 +12,18 @@ pub fn scan(dir: &Path) -> (Vec<DirEntry>, Vec<DirEntry>) {
         .min_depth(1)
         .sort_by_file_name();
 
-    for entry in walker {
-        if let Ok(e) = entry {
-            if e.file_type().is_dir() {
-                let dir_name = e.path().file_name().unwrap();
-                if dir_name == "preview" {
-                    continue;
-                }
-                if dir_name == "NKSC_PARAM" {
-                    continue
-                }
-                dirs.push(e);
-            } else {
-                files.push(e)
+    for e in walker.into_iter().flatten() {
+        if e.file_type().is_dir() {
+            let dir_name = e.path().file_name().unwrap();
+            if dir_name == "preview" {
+                continue;
             }
+            if dir_name == "NKSC_PARAM" {
+                continue
+            }
+            dirs.push(e);
+        } else {
+            files.push(e)
         }
     }
     (files, dirs)
@@ -0,0 +1,277 @@
+use crate::core::config;
+use crate::core::fninfo::Info;
+
+/// Layouts the commands used before templates, kept as their defaults.
+pub const DEFAULT: &str = "{date}/{name}.{ext}";
+pub const COMPACT: &str = "{date}/{compact}.{ext}";
+pub const YEAR: &str = "{year}/{date}/{name}.{ext}";
+pub const YEAR_COMPACT: &str = "{year}/{date}/{compact}.{ext}";
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Token {
+    Year,
+    Month,
+    Day,
+    Date,
+    Time,
+    Datetime,
+    Millis,
+    Name,
+    Compact,
+    Number,
+    Model,
+    Ext,
+    Lens,
+    LensSpec,
+    Focal,
+    Aperture,
+    Shutter,
+    Iso,
+    Serial,
+    Rating,
+    Seq,
+}
+
+impl Token {
+    fn parse(s: &str) -> Option<Token> {
+        Some(match s {
+            "year" => Token::Year,
+            "month" => Token::Month,
+            "day" => Token::Day,
+            "date" => Token::Date,
+            "time" => Token::Time,
+            "datetime" => Token::Datetime,
+            "millis" => Token::Millis,
+            "name" => Token::Name,
+            "compact" => Token::Compact,
+            "number" => Token::Number,
+            "model" => Token::Model,
+            "ext" => Token::Ext,
+            "lens" => Token::Lens,
+            "lensspec" => Token::LensSpec,
+            "focal" => Token::Focal,
+            "aperture" => Token::Aperture,
+            "shutter" => Token::Shutter,
+            "iso" => Token::Iso,
+            "serial" => Token::Serial,
+            "rating" => Token::Rating,
+            "seq" => Token::Seq,
+            _ => return None,
+        })
+    }
+
+    fn value(&self, info: &Info, seq: usize) -> Option<String> {
+        let dt = |f| Some(info.datetime.format(f).to_string());
+        match self {
+            Token::Year => dt("%Y"),
+            Token::Month => dt("%m"),
+            Token::Day => dt("%d"),
+            Token::Date => Some(info.to_date()),
+            Token::Time => Some(info.to_time()),
+            Token::Datetime => Some(info.to_datetime()),
+            Token::Millis => info.millis.map(|x| format!("{x:03}")),
+            Token::Name => Some(info.to_name()),
+            Token::Compact => Some(info.to_compact_name()),
+            Token::Number => Some(info.number.clone()),
+            Token::Model => Some(info.model.clone()),
+            Token::Ext => Some(info.ext.clone()),
+            Token::Lens => info.exposure.lens.clone(),
+            Token::LensSpec => info.exposure.lens_spec_text(),
+            Token::Focal => info.exposure.focal_text(),
+            Token::Aperture => info.exposure.aperture_text(),
+            Token::Shutter => info.exposure.shutter_text(),
+            Token::Iso => info.exposure.iso_text(),
+            Token::Serial => info.serial.clone(),
+            Token::Rating => info.rating.map(|x| x.to_string()),
+            Token::Seq => Some(format!("{seq:04}")),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Part {
+    Text(String),
+    Token(Token, Option<String>),
+}
+
+#[derive(thiserror::Error, Debug)]
+pub enum TemplateError {
+    #[error("template-error: unclosed brace in {0}")]
+    Unclosed(String),
+    #[error("template-error: unknown token {{{0}}}")]
+    Unknown(String),
+}
+
+/// A naming layout such as `{year}/{date}/{time}__{number}__{model}.{ext}`,
+/// rendered relative to the destination. `{serial|NOSERIAL}` gives a value for
+/// files that lack one; otherwise a missing value renders empty. A directory
+/// or name left empty, or rendered as `.` or `..`, becomes `UNSET`, so that
+/// every path stays under the destination.
+#[derive(Clone, Debug)]
+pub struct Template {
+    parts: Vec<Part>,
+}
+
+const UNSET: &str = "UNSET";
+
+/// Values become part of a path: anything but letters, digits and
+/// `-._+` turns into `-`.
+fn clean(value: &str) -> String {
+    value
+        .trim()
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || "-._+".contains(c) {
+                c
+            } else {
+                '-'
+            }
+        })
+        .collect()
+}
+
+impl Template {
+    pub fn parse(s: &str) -> Result<Template, TemplateError> {
+        let mut parts = Vec::new();
+        let mut rest = s;
+        while let Some(open) = rest.find('{') {
+            if open > 0 {
+                parts.push(Part::Text(rest[..open].to_string()));
+            }
+            let close = rest[open..]
+                .find('}')
+                .ok_or_else(|| TemplateError::Unclosed(s.to_string()))?;
+            let body = &rest[open + 1..open + close];
+            let (name, default) = match body.split_once('|') {
+                Some((name, default)) => (name, Some(default.to_string())),
+                None => (body, None),
+            };
+            let token = Token::parse(name).ok_or_else(|| TemplateError::Unknown(name.to_string()))?;
+            parts.push(Part::Token(token, default));
+            rest = &rest[open + close + 1..];
+        }
+        if !rest.is_empty() {
+            parts.push(Part::Text(rest.to_string()));
+        }
+        Ok(Template { parts })
+    }
+
+    /// The template given on the command line, else the one in the
+    /// config, else `default`.
+    pub fn resolve(cli: Option<&str>, default: &str) -> Result<Template, TemplateError> {
+        let s = cli
+            .or(config::get().template.as_deref())
+            .unwrap_or(default);
+        Template::parse(s)
+    }
+
+    /// `seq` is the position of the file in this run, for `{seq}`.
+    pub fn render(&self, info: &Info, seq: usize) -> String {
+        let mut out = String::new();
+        for part in &self.parts {
+            match part {
+                Part::Text(text) => out.push_str(text),
+                Part::Token(token, default) => {
+                    if let Some(value) = token.value(info, seq).or_else(|| default.clone()) {
+                        out.push_str(&clean(&value));
+                    }
+                }
+            }
+        }
+        out.split('/')
+            .map(|x| if matches!(x, "" | "." | "..") { UNSET } else { x })
+            .collect::<Vec<_>>()
+            .join("/")
+    }
+
+    /// The file name part without a trailing `.{ext}`, for commands that
+    /// rename in place and keep each file's own extension.
+    pub fn file_stem(&self) -> Template {
+        let start = self
+            .parts
+            .iter()
+            .rposition(|x| matches!(x, Part::Text(text) if text.contains('/')));
+        let mut parts: Vec<Part> = match start {
+            Some(i) => {
+                let Part::Text(text) = &self.parts[i] else {
+                    unreachable!()
+                };
+                let tail = text.rsplit('/').next().unwrap().to_string();
+                let head = (!tail.is_empty()).then_some(Part::Text(tail));
+                head.into_iter().chain(self.parts[i + 1..].iter().cloned()).collect()
+            }
+            None => self.parts.clone(),
+        };
+        if let [.., Part::Text(dot), Part::Token(Token::Ext, _)] = &parts[..] {
+            if dot.ends_with('.') {
+                let dot = dot[..dot.len() - 1].to_string();
+                parts.truncate(parts.len() - 2);
+                if !dot.is_empty() {
+                    parts.push(Part::Text(dot));
+                }
+            }
+        }
+        Template { parts }
+    }
+
+    /// Whether rendering needs fields only EXIF has, not the file name.
+    pub fn needs_exif(&self) -> bool {
+        self.parts.iter().any(|x| {
+            matches!(
+                x,
+                Part::Token(
+                    Token::Lens
+                        | Token::LensSpec
+                        | Token::Focal
+                        | Token::Aperture
+                        | Token::Shutter
+                        | Token::Iso
+                        | Token::Serial
+                        | Token::Rating,
+                    _
+                )
+            )
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render() {
+        let mut info = Info::from_path("20230105_150108__03212__A1.ARW").unwrap();
+        info.exposure.lens = Some("FE 24-70mm F2.8 GM II".to_string());
+        info.exposure.focal_length = Some(35.0);
+        info.exposure.exposure_time = Some(0.004);
+
+        let t = Template::parse(YEAR).unwrap();
+        assert_eq!(t.render(&info, 1), "2023/20230105/20230105_150108__03212__A1.ARW");
+        assert!(!t.needs_exif());
+        let t = Template::parse("{month}/{time}_{seq}_{millis|000}_{serial|X}.{ext}").unwrap();
+        assert_eq!(t.render(&info, 7), "01/150108_0007_000_X.ARW");
+        assert!(t.needs_exif());
+        info.serial = Some("50 12345".to_string());
+        assert_eq!(t.render(&info, 7), "01/150108_0007_000_50-12345.ARW");
+        let t = Template::parse("{focal}mm/{shutter}s_{iso|ISO}_{lens}.{ext}").unwrap();
+        assert_eq!(t.render(&info, 1), "35mm/1-250s_ISO_FE-24-70mm-F2.8-GM-II.ARW");
+        assert!(t.needs_exif());
+
+        // nothing may land outside the destination
+        let t = Template::parse("{lens}/{rating}/{name}.{ext}").unwrap();
+        info.exposure.lens = None;
+        assert_eq!(t.render(&info, 1), "UNSET/UNSET/20230105_150108__03212__A1.ARW");
+        info.exposure.lens = Some("..".to_string());
+        info.rating = Some(4);
+        assert_eq!(t.render(&info, 1), "UNSET/4/20230105_150108__03212__A1.ARW");
+        assert!(t.needs_exif());
+        let t = Template::parse("/../{name}.{ext}").unwrap();
+        assert_eq!(t.render(&info, 1), "UNSET/UNSET/20230105_150108__03212__A1.ARW");
+
+        let stem = Template::parse(COMPACT).unwrap().file_stem();
+        assert_eq!(stem.render(&info, 1), "150108__03212__A1");
+        assert!(matches!(Template::parse("{date"), Err(TemplateError::Unclosed(_))));
+        assert!(matches!(Template::parse("{colour}"), Err(TemplateError::Unknown(_))));
+    }
+}
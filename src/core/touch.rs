@@ -1,12 +1,17 @@
 use std::{path::Path, time::SystemTime};
 
-pub fn touch_form_0(path: &str, date_str: &str) -> Result<(), std::io::Error> {
-    let date = chrono::NaiveDate::parse_from_str(date_str, "%Y%m%d").unwrap();
-    let seconds = date
-        .and_hms_opt(0, 0, 0)
-        .unwrap()
-        .and_local_timezone(chrono::offset::Local)
-        .unwrap()
+use crate::core::zone::{self, Zone};
+
+fn no_such_time(date_str: &str) -> std::io::Error {
+    std::io::Error::other(format!("no such local time: {date_str}"))
+}
+
+/// Set a day folder to midnight of `date_str` (`YYYYMMDD`) in `zone`.
+pub fn touch_form_0(path: &str, date_str: &str, zone: &Zone) -> Result<(), std::io::Error> {
+    let date = chrono::NaiveDate::parse_from_str(date_str, "%Y%m%d").map_err(std::io::Error::other)?;
+    let seconds = zone
+        .instant(date.and_hms_opt(0, 0, 0).unwrap())
+        .ok_or_else(|| no_such_time(date_str))?
         .timestamp();
     let ftime = filetime::FileTime::from_unix_time(seconds, 0);
     filetime::set_file_times(path, ftime, ftime)
@@ -22,11 +27,10 @@ pub fn _touch_with_filename(path: &str) -> Result<(), std::io::Error> {
 
     let date_str = &file_stem.to_str().unwrap().to_string()[0..15];
     let date = chrono::NaiveDateTime::parse_from_str(date_str, "%Y%m%d_%H%M%S")
-        .map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, e))?;
+        .map_err(std::io::Error::other)?;
 
-    let seconds = date
-        .and_local_timezone(chrono::offset::Local)
-        .unwrap()
+    let seconds = zone::resolve(&chrono::offset::Local, date)
+        .ok_or_else(|| no_such_time(date_str))?
         .timestamp();
     let ftime = filetime::FileTime::from_unix_time(seconds, 0);
     filetime::set_file_times(path, ftime, ftime)
@@ -47,11 +51,10 @@ pub fn _touch_by_filename(target: &str, path: &str) -> Result<(), std::io::Error
 
     let date_str = &file_stem.to_str().unwrap().to_string()[0..15];
     let date = chrono::NaiveDateTime::parse_from_str(date_str, "%Y%m%d_%H%M%S")
-        .map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, e))?;
+        .map_err(std::io::Error::other)?;
 
-    let seconds = date
-        .and_local_timezone(chrono::offset::Local)
-        .unwrap()
+    let seconds = zone::resolve(&chrono::offset::Local, date)
+        .ok_or_else(|| no_such_time(date_str))?
         .timestamp();
     let ftime = filetime::FileTime::from_unix_time(seconds, 0);
     filetime::set_file_times(target, ftime, ftime)
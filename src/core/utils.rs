@@ -1,14 +1,57 @@
 use lazy_static::lazy_static;
 use regex::Regex;
+use std::path::Path;
 
 pub fn env_var(key: &str) -> Option<String> {
     std::env::var_os(key).map(|e| e.into_string().unwrap())
 }
 
 lazy_static! {
-    static ref IMG_PEXT: Regex = Regex::new(r"jpeg|jpg|heif|heic|arw|dng|nef").unwrap();
+    static ref IMG_PEXT: Regex = Regex::new(r"jpeg|jpg|heif|heic|hif|arw|dng|nef").unwrap();
+    static ref VIDEO_PEXT: Regex = Regex::new(r"^(mp4|mov|mts|m2ts|avi|mxf|3gp)$").unwrap();
+    static ref SIDECAR_PEXT: Regex = Regex::new(r"^(xmp|xml|thm)$").unwrap();
 }
 
 pub fn is_img_ext<T: AsRef<str>>(ext: T) -> bool {
     IMG_PEXT.is_match(ext.as_ref())
 }
+
+pub fn is_video_ext<T: AsRef<str>>(ext: T) -> bool {
+    VIDEO_PEXT.is_match(ext.as_ref())
+}
+
+pub fn is_sidecar_ext<T: AsRef<str>>(ext: T) -> bool {
+    SIDECAR_PEXT.is_match(ext.as_ref())
+}
+
+/// Bytes available to unprivileged users on the filesystem holding `path`,
+/// measured at the closest ancestor that exists.
+pub fn available_space<P: AsRef<Path>>(path: P) -> std::io::Result<u64> {
+    use std::os::unix::ffi::OsStrExt;
+
+    // a relative path runs out at "", which is the current directory
+    let path = path
+        .as_ref()
+        .ancestors()
+        .map(|p| if p.as_os_str().is_empty() { Path::new(".") } else { p })
+        .find(|p| p.exists())
+        .unwrap_or(Path::new("/"));
+    let c_path = std::ffi::CString::new(path.as_os_str().as_bytes())?;
+    let mut stat: libc::statvfs = unsafe { std::mem::zeroed() };
+    if unsafe { libc::statvfs(c_path.as_ptr(), &mut stat) } != 0 {
+        return Err(std::io::Error::last_os_error());
+    }
+    #[allow(clippy::unnecessary_cast)]
+    Ok(stat.f_bavail as u64 * stat.f_frsize as u64)
+}
+
+pub fn human_bytes(n: u64) -> String {
+    const UNITS: [&str; 5] = ["B", "KiB", "MiB", "GiB", "TiB"];
+    let mut value = n as f64;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < UNITS.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+    format!("{value:.1} {}", UNITS[unit])
+}
@@ -0,0 +1,127 @@
+use std::sync::OnceLock;
+
+use chrono::{DateTime, Duration, FixedOffset, LocalResult, NaiveDateTime, TimeZone, Utc};
+use chrono_tz::Tz;
+
+/// Where a wall-clock capture time without its own offset was taken.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum Zone {
+    #[default]
+    Local,
+    Fixed(FixedOffset),
+    Named(Tz),
+}
+
+static ZONE: OnceLock<Zone> = OnceLock::new();
+
+/// Use `zone`, from `--tz`, for the rest of the run.
+pub fn set(zone: Zone) {
+    let _ = ZONE.set(zone);
+}
+
+/// The `--tz` zone of the run, or `Local` when `set` was never called.
+pub fn get() -> Zone {
+    ZONE.get().copied().unwrap_or_default()
+}
+
+/// Parse `--tz`: `local`, `utc`, an offset like `+09:00` or `-0530`, or an
+/// IANA name like `Asia/Tokyo`.
+pub fn parse(s: &str) -> Result<Zone, String> {
+    match s {
+        "local" => return Ok(Zone::Local),
+        "utc" | "UTC" | "Z" => return Ok(Zone::Fixed(FixedOffset::east_opt(0).unwrap())),
+        _ => (),
+    }
+    if let Some(offset) = parse_offset(s) {
+        return Ok(Zone::Fixed(offset));
+    }
+    s.parse::<Tz>()
+        .map(Zone::Named)
+        .map_err(|_| format!("invalid time zone: {s}"))
+}
+
+/// An EXIF `OffsetTime*` value such as `+09:00`; also takes `+0900` and `+9`.
+pub fn parse_offset(s: &str) -> Option<FixedOffset> {
+    let s = s.trim();
+    let sign = match s.get(..1)? {
+        "+" => 1,
+        "-" => -1,
+        _ => return None,
+    };
+    let digits = s[1..].replace(':', "");
+    if digits.is_empty() || digits.len() > 4 || !digits.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    let (hours, minutes) = match digits.len() {
+        1 | 2 => (digits.parse::<i32>().ok()?, 0),
+        n => (digits[..n - 2].parse().ok()?, digits[n - 2..].parse().ok()?),
+    };
+    if minutes >= 60 {
+        return None;
+    }
+    FixedOffset::east_opt(sign * (hours * 3600 + minutes * 60))
+}
+
+/// Place a wall-clock time in `tz` without panicking. A time repeated when
+/// the clocks go back takes its first occurrence; a time skipped when they
+/// go forward is read as if the clock had not been changed yet.
+pub fn resolve<T: TimeZone>(tz: &T, naive: NaiveDateTime) -> Option<DateTime<T>> {
+    match tz.from_local_datetime(&naive) {
+        LocalResult::Single(x) => Some(x),
+        LocalResult::Ambiguous(earliest, _) => Some(earliest),
+        LocalResult::None => tz.from_local_datetime(&(naive + Duration::hours(1))).earliest(),
+    }
+}
+
+impl Zone {
+    pub fn instant(&self, naive: NaiveDateTime) -> Option<DateTime<Utc>> {
+        match self {
+            Zone::Local => resolve(&chrono::Local, naive).map(|x| x.with_timezone(&Utc)),
+            Zone::Fixed(offset) => resolve(offset, naive).map(|x| x.with_timezone(&Utc)),
+            Zone::Named(tz) => resolve(tz, naive).map(|x| x.with_timezone(&Utc)),
+        }
+    }
+
+    /// An instant, such as a UTC movie or GPS time, on this zone's clock.
+    pub fn wall_clock(&self, time: DateTime<Utc>) -> DateTime<FixedOffset> {
+        match self {
+            Zone::Local => time.with_timezone(&chrono::Local).fixed_offset(),
+            Zone::Fixed(offset) => time.with_timezone(offset),
+            Zone::Named(tz) => time.with_timezone(tz).fixed_offset(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(zone: &str, s: &str) -> i64 {
+        let naive = NaiveDateTime::parse_from_str(s, "%Y%m%d_%H%M%S").unwrap();
+        parse(zone).unwrap().instant(naive).unwrap().timestamp()
+    }
+
+    #[test]
+    fn test_parse_and_resolve() {
+        assert_eq!(parse_offset("+09:00"), FixedOffset::east_opt(9 * 3600));
+        assert_eq!(parse_offset("-0530"), FixedOffset::east_opt(-(5 * 3600 + 30 * 60)));
+        assert_eq!(parse_offset("9:00"), None);
+        assert_eq!(at("utc", "19700101_000100"), 60);
+        assert!(parse("Mars/Olympus").is_err());
+
+        // Berlin: 02:30 does not exist on 2023-03-26 and happens twice on 2023-10-29
+        let berlin = "Europe/Berlin";
+        assert_eq!(at(berlin, "20230326_023000"), at(berlin, "20230326_033000"));
+        assert_eq!(at(berlin, "20231029_023000"), at("+02:00", "20231029_023000"));
+    }
+
+    #[test]
+    fn test_wall_clock() {
+        let utc = Utc.with_ymd_and_hms(2023, 1, 5, 6, 1, 8).unwrap();
+        let tokyo = parse("Asia/Tokyo").unwrap().wall_clock(utc);
+        assert_eq!(tokyo.to_rfc3339(), "2023-01-05T15:01:08+09:00");
+        let summer = Utc.with_ymd_and_hms(2023, 7, 5, 6, 1, 8).unwrap();
+        assert_eq!(parse("Europe/Berlin").unwrap().wall_clock(summer).to_rfc3339(), "2023-07-05T08:01:08+02:00");
+        assert_eq!(parse("-0530").unwrap().wall_clock(utc).to_rfc3339(), "2023-01-05T00:31:08-05:30");
+    }
+}
@@ -0,0 +1,76 @@
+use clap::Parser;
+
+use crate::cmd::{Cmd, CmdResult};
+use crate::core::config;
+use crate::core::fninfo::{Info, InfoErr};
+
+// ==== COMMAND ====
+#[derive(Parser, Debug)]
+pub struct ClockCommand {
+    #[arg(help = "photo from the body with the right time.")]
+    reference: String,
+    #[arg(help = "photo of the same moment from the body to correct.")]
+    other: String,
+}
+
+impl Cmd for ClockCommand {
+    fn run(self) -> CmdResult {
+        let resp = do_clock(&self.reference, &self.other)?;
+        println!(
+            "[CLOCK] {} is {}s {} {}",
+            resp.model,
+            resp.offset.abs(),
+            if resp.offset < 0 { "ahead of" } else { "behind" },
+            resp.reference
+        );
+        if let Some(current) = config::get().clock.get(&resp.model) {
+            println!("[CLOCK] currently configured: {current}");
+        }
+        println!("[clock]\n\"{}\" = {}", resp.model, resp.offset);
+        Ok(())
+    }
+}
+
+// ==== TASK ====
+#[derive(thiserror::Error, Debug)]
+pub enum ClockError {
+    #[error("meta-error: {0}")]
+    Meta(#[from] InfoErr),
+    #[error("same-body: both photos come from {0}")]
+    SameBody(String),
+    #[error("no-clock: {0} has no capture time from the camera, only {1}")]
+    NoClock(String, String),
+}
+
+pub struct Response {
+    pub reference: String,
+    pub model: String,
+    /// Seconds to add to the other body's clock.
+    pub offset: i64,
+}
+
+/// Clock correction for the body of `other`, from two photos of one moment.
+/// Times are compared as the cameras recorded them, before any correction.
+pub fn do_clock(reference: &str, other: &str) -> Result<Response, ClockError> {
+    let ref_info = Info::from_exif_raw(reference)?;
+    let other_info = Info::from_exif_raw(other)?;
+    for (path, info) in [(reference, &ref_info), (other, &other_info)] {
+        if !info.source.is_camera_clock() {
+            return Err(ClockError::NoClock(path.to_string(), info.source.to_string()));
+        }
+    }
+    if ref_info.model == other_info.model {
+        return Err(ClockError::SameBody(ref_info.model));
+    }
+    let ref_time = ref_info.timestamp();
+    let other_time = other_info.timestamp();
+    println!("[CLOCK] {} {}", ref_info.model, ref_time.format("%Y-%m-%d %H:%M:%S%.3f"));
+    println!("[CLOCK] {} {}", other_info.model, other_time.format("%Y-%m-%d %H:%M:%S%.3f"));
+
+    let millis = (ref_time - other_time).num_milliseconds();
+    Ok(Response {
+        reference: ref_info.model,
+        model: other_info.model,
+        offset: (millis as f64 / 1000.0).round() as i64,
+    })
+}
@@ -1,16 +1,44 @@
+use std::collections::{HashMap, HashSet};
+use std::fmt;
 use std::fs::{self};
 use std::path::{Path, PathBuf};
-use std::time::Instant;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{mpsc, Mutex};
+use std::thread;
+use std::time::{Instant, SystemTime};
 
+use chrono::NaiveDate;
 use clap::Parser;
+use serde::Serialize;
+use walkdir::WalkDir;
 
 use crate::cmd::{Cmd, CmdResult};
+use crate::core::cardstate::{self, CardState};
+use crate::core::checksum;
+use crate::core::journal::{Journal, State, JOURNAL_FILE};
+use crate::core::library::{Library, INDEX_FILE};
+use crate::core::mounts;
 use crate::core::{fninfo, utils};
 use crate::core::progress::Progress;
+use crate::core::template::{self, Template};
+use crate::core::zone::{self, Zone};
 
 // use kdam::{term, tqdm, BarExt, Column, RichProgress, Spinner};
 use kdam::{term, tqdm, BarExt};
-use std::io::{stderr, IsTerminal};
+use std::io::{stderr, stdin, IsTerminal};
+
+/// Set when stdout carries the JSON plan; status lines then go to stderr.
+static JSON: AtomicBool = AtomicBool::new(false);
+
+macro_rules! say {
+    ($($arg:tt)*) => {
+        if JSON.load(Ordering::Relaxed) {
+            eprintln!($($arg)*)
+        } else {
+            println!($($arg)*)
+        }
+    };
+}
 
 // ==== COMMAND ====
 #[derive(Parser)]
@@ -21,23 +49,128 @@ pub struct ImportCommand {
     host: String,
     #[arg(long, short, default_value_t = false)]
     compact: bool,
+    #[arg(help = "naming layout, e.g. {year}/{date}/{time}__{number}__{model}.{ext}.")]
+    #[arg(long)]
+    template: Option<String>,
     #[arg(help = "disable touch file timestamp.")]
     #[arg(short, long = "no-touch", default_value_t = true)]
     #[arg(action=clap::ArgAction::SetFalse)]
     touch: bool,
-    #[arg(long, short, default_value_t = false)]
+    #[arg(help = "move files off the card: copy, verify, fsync, then delete the source.")]
+    #[arg(long, short, visible_alias = "move", default_value_t = false)]
     rename: bool,
+    #[arg(help = "another card to import from, e.g. the second slot; merged by content.")]
+    #[arg(long, value_name = "SOURCE")]
+    also: Vec<String>,
+    #[arg(help = "second destination, e.g. a backup disk, written from the same read.")]
+    #[arg(long, value_name = "DEST")]
+    mirror: Option<String>,
+    #[arg(help = "number of files copied at once.")]
+    #[arg(long, short, default_value_t = 1)]
+    jobs: usize,
+    #[arg(help = "only import these extensions, e.g. ARW,JPG.")]
+    #[arg(long, value_delimiter = ',')]
+    include: Vec<String>,
+    #[arg(help = "never import these extensions.")]
+    #[arg(long, value_delimiter = ',')]
+    exclude: Vec<String>,
+    #[arg(help = "only import shots taken on or after this day (YYYYMMDD, today, yesterday).")]
+    #[arg(long, value_parser = parse_day)]
+    since: Option<NaiveDate>,
+    #[arg(help = "only import shots taken on or before this day.")]
+    #[arg(long, value_parser = parse_day)]
+    until: Option<NaiveDate>,
+    #[arg(help = "only import shots from these models, e.g. A1,Z8.")]
+    #[arg(long, value_delimiter = ',')]
+    model: Vec<String>,
+    #[arg(help = "when the destination is too small, import what fits, oldest first.")]
+    #[arg(long, default_value_t = false)]
+    force: bool,
+    #[arg(help = "show what would be copied, created and touched, without writing anything.")]
+    #[arg(long, short, default_value_t = false)]
+    dry: bool,
+    #[arg(help = "print the --dry plan as JSON.")]
+    #[arg(long, default_value_t = false, requires = "dry")]
+    json: bool,
+    #[arg(help = "time zone of capture times without a recorded offset: local, utc, +09:00, Asia/Tokyo.")]
+    #[arg(long, default_value = "local", value_parser = zone::parse)]
+    tz: Zone,
+    #[arg(help = "mount table used to find camera cards.")]
+    #[arg(long, default_value_t = String::from(mounts::PROC_MOUNTS))]
+    mounts: String,
+}
+
+fn parse_day(s: &str) -> Result<NaiveDate, String> {
+    let today = chrono::Local::now().date_naive();
+    match s {
+        "today" => Ok(today),
+        "yesterday" => Ok(today - chrono::Duration::days(1)),
+        _ => NaiveDate::parse_from_str(s, "%Y%m%d")
+            .or_else(|_| NaiveDate::parse_from_str(s, "%Y-%m-%d"))
+            .map_err(|_| format!("invalid day: {s}")),
+    }
+}
+
+fn cmd_ext_list(exts: &[String]) -> Vec<String> {
+    exts.iter()
+        .map(|x| x.trim_start_matches('.').to_ascii_lowercase())
+        .collect()
+}
+
+
+fn cmd_find_cards(cmd: &ImportCommand) -> R<Vec<PathBuf>> {
+    if cfg!(target_os = "macos") {
+        return Ok(mounts::volume_cards("/Volumes"));
+    }
+    let table = mounts::read(&cmd.mounts)
+        .map_err(|_| io_error("mounts".to_string(), cmd.mounts.clone()))?;
+    Ok(mounts::cards(&table, &mounts::MEDIA_ROOTS, Path::new(mounts::SYS_BLOCK))
+        .into_iter()
+        .map(|m| m.point)
+        .collect())
+}
+
+fn cmd_pick_card(cards: Vec<PathBuf>) -> R<PathBuf> {
+    if cards.len() == 1 {
+        return Ok(cards.into_iter().next().unwrap());
+    }
+    if cards.is_empty() {
+        return Err(E::Source("no camera card found".to_string()));
+    }
+    for (i, card) in cards.iter().enumerate() {
+        say!("[CARD] {}: {}", i + 1, card.to_str().unwrap());
+    }
+    if !stdin().is_terminal() {
+        return Err(E::Source(format!("{} camera cards found, pass one as source", cards.len())));
+    }
+    say!("select card [1-{}]:", cards.len());
+    let mut line = String::new();
+    stdin()
+        .read_line(&mut line)
+        .map_err(|_| io_error("stdin".to_string(), String::new()))?;
+    match line.trim().parse::<usize>() {
+        Ok(n) if (1..=cards.len()).contains(&n) => Ok(cards[n - 1].clone()),
+        _ => Err(E::Source(format!("invalid selection: {}", line.trim()))),
+    }
 }
 
+/// Card identity for the per-card import state, from the volume UUID when
+/// the source's mount has one and from its earliest files otherwise.
+fn cmd_card_id(cmd: &ImportCommand, source: &Path) -> Option<String> {
+    let source = fs::canonicalize(source).ok()?;
+    let table = mounts::read(&cmd.mounts).unwrap_or_default();
+    let volume = mounts::mount_of(&table, &source)
+        .and_then(|m| Some((mounts::volume_uuid(&m.device)?, m.point.clone())));
+    cardstate::card_id(volume.as_ref().map(|(uuid, point)| (uuid.as_str(), point.as_path())), &source)
+}
 
-fn cmd_import_source_dir(cmd: &ImportCommand) -> String {
+fn cmd_import_source_dir(cmd: &ImportCommand) -> R<String> {
     match cmd.source.as_ref() {
-        Some(s) => s.clone(),
-        #[rustfmt::skip]
-        _ => match cmd.host.as_str() {
-            "mac" => "/Volumns/Untitled",
-            _ => "/Volumns/Untitled",
-        }.to_string(),
+        Some(s) => Ok(s.clone()),
+        _ => {
+            let card = cmd_pick_card(cmd_find_cards(cmd)?)?;
+            Ok(card.to_str().unwrap().to_string())
+        }
     }
 }
 
@@ -47,12 +180,8 @@ fn cmd_import_dest_dir(cmd: &ImportCommand) -> String {
         s.clone()
     } else {
         let home = utils::env_var("HOME").unwrap();
-        match cmd.host.as_str() {
-            // "mac" => format!("{home}/PI"),
-            // "hi" => format!("{home}/PI"),
-            // "mi2" => format!("{home}/PI"),
-            _ => format!("{home}/PI"),
-        }
+        // "mac" | "hi" | "mi2" => format!("{home}/PI"),
+        format!("{home}/PI")
     }
 }
 
@@ -60,39 +189,232 @@ fn cmd_import_dest_dir(cmd: &ImportCommand) -> String {
 impl Cmd for ImportCommand {
     fn run(self) -> CmdResult {
         let cmd = &self;
-        let source = PathBuf::from(cmd_import_source_dir(cmd));
+        JSON.store(cmd.json, Ordering::Relaxed);
+        zone::set(cmd.tz);
+        let source = PathBuf::from(cmd_import_source_dir(cmd)?);
         let dest = PathBuf::from(cmd_import_dest_dir(cmd));
         // let compact = cmd.compact;
+        let default = if cmd.compact { template::COMPACT } else { template::DEFAULT };
+        let template = Template::resolve(cmd.template.as_deref(), default)?;
         // let touch = cmd.touch;
 
-        println!("name:{:?}, source:{:?}, dest:{:?}", cmd.host, source, dest);
+        say!("name:{:?}, source:{:?}, dest:{:?}", cmd.host, source, dest);
+        let sources = std::iter::once(source)
+            .chain(cmd.also.iter().map(PathBuf::from))
+            .map(|path| Source {
+                card: cmd_card_id(cmd, &path),
+                path,
+            })
+            .collect();
         let mut req = Request {
-            source,
+            sources,
             dest,
-            compact: cmd.compact,
+            template,
             touch: cmd.touch,
             rename: cmd.rename,
+            jobs: cmd.jobs.max(1),
+            include: cmd_ext_list(&cmd.include),
+            exclude: cmd_ext_list(&cmd.exclude),
+            force: cmd.force,
+            mirror: cmd.mirror.as_ref().map(PathBuf::from),
+            dry: cmd.dry,
+            tz: cmd.tz,
+            filter: Filter {
+                since: cmd.since,
+                until: cmd.until,
+                models: cmd.model.clone(),
+            },
         };
-        do_import(&mut req)?;
+        let resp = do_import(&mut req)?;
+        if cmd.json {
+            println!("{}", serde_json::to_string_pretty(&resp.plan)?);
+        } else {
+            for step in &resp.plan {
+                println!("[PLAN] {step}");
+            }
+        }
+        if !resp.mismatched.is_empty() {
+            return Err(Box::new(E::Verify(resp.mismatched.len())));
+        }
+        if let Some(mirror) = &resp.mirror {
+            if !mirror.mismatched.is_empty() {
+                return Err(Box::new(E::Verify(mirror.mismatched.len())));
+            }
+            if !mirror.failed.is_empty() {
+                return Err(Box::new(E::Mirror(mirror.failed.len())));
+            }
+        }
         Ok(())
     }
 }
 
 // ==== TASK ====
+pub struct Source {
+    pub path: PathBuf,
+    pub card: Option<String>,
+}
+
 pub struct Request {
-    pub source: PathBuf,
+    pub sources: Vec<Source>,
     pub dest: PathBuf,
-    pub compact: bool,
+    pub template: Template,
     pub touch: bool,
     pub rename: bool,
+    pub jobs: usize,
+    pub include: Vec<String>,
+    pub exclude: Vec<String>,
+    pub force: bool,
+    pub mirror: Option<PathBuf>,
+    pub dry: bool,
+    pub tz: Zone,
+    pub filter: Filter,
+}
+
+/// Selection by capture day and camera model, applied to the planned
+/// `Info` before anything is copied.
+#[derive(Default)]
+pub struct Filter {
+    pub since: Option<NaiveDate>,
+    pub until: Option<NaiveDate>,
+    pub models: Vec<String>,
+}
+
+impl Filter {
+    pub fn matches(&self, info: &fninfo::Info) -> bool {
+        if !self.models.is_empty() && !self.models.iter().any(|m| m.eq_ignore_ascii_case(&info.model)) {
+            return false;
+        }
+        if self.since.is_none() && self.until.is_none() {
+            return true;
+        }
+        let day = info.date();
+        self.since.is_none_or(|x| day >= x) && self.until.is_none_or(|x| day <= x)
+    }
 }
 
-pub struct Response {}
+pub enum Kind {
+    Media,
+    Sidecar,
+}
+
+impl Request {
+    /// Decide whether a source file is imported, by lower-case extension.
+    pub fn kind(&self, path: &Path) -> Result<Kind, &'static str> {
+        let ext = path
+            .extension()
+            .and_then(|x| x.to_str())
+            .ok_or("no-ext")?
+            .to_ascii_lowercase();
+        if !self.include.is_empty() && !self.include.contains(&ext) {
+            return Err("excluded");
+        }
+        if self.exclude.contains(&ext) {
+            return Err("excluded");
+        }
+        if utils::is_sidecar_ext(&ext) {
+            Ok(Kind::Sidecar)
+        } else if utils::is_img_ext(&ext) || utils::is_video_ext(&ext) {
+            Ok(Kind::Media)
+        } else {
+            Err("unsupported")
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct Response {
+    pub imported: usize,
+    pub moved: usize,
+    pub bytes: u64,
+    pub skipped: usize,
+    pub filtered: usize,
+    pub duplicates: Vec<(String, String)>,
+    pub mismatched: Vec<String>,
+    pub collisions: Vec<(String, String)>,
+    pub merged: usize,
+    pub single: Vec<String>,
+    pub ignored: Vec<(String, &'static str)>,
+    pub mirror: Option<MirrorResponse>,
+    pub plan: Vec<Step>,
+}
+
+/// One action of a dry run, in the order a real import would take it.
+#[derive(Serialize)]
+#[serde(tag = "action", rename_all = "lowercase")]
+pub enum Step {
+    Mkdir { path: String },
+    Touch { path: String, time: String },
+    Copy { src: String, dest: String, size: u64 },
+    Skip { src: String, dest: String },
+    Duplicate { src: String, existing: String },
+    Fail { dest: String, why: String },
+    Remove { src: String },
+}
+
+impl fmt::Display for Step {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Step::Mkdir { path } => write!(f, "mkdir {path}"),
+            Step::Touch { path, time } => write!(f, "touch {path} {time}"),
+            Step::Copy { src, dest, size } => write!(f, "copy {src} -> {dest} ({})", utils::human_bytes(*size)),
+            Step::Skip { src, dest } => write!(f, "skip {src} = {dest}"),
+            Step::Duplicate { src, existing } => write!(f, "duplicate {src} = {existing}"),
+            Step::Fail { dest, why } => write!(f, "fail {dest}: {why}"),
+            Step::Remove { src } => write!(f, "remove {src}"),
+        }
+    }
+}
+
+fn local_time(time: SystemTime) -> String {
+    chrono::DateTime::<chrono::Local>::from(time)
+        .format("%Y-%m-%d %H:%M:%S")
+        .to_string()
+}
+
+/// Results on the mirror destination. Failures there are collected here
+/// instead of aborting the import.
+#[derive(Default)]
+pub struct MirrorResponse {
+    pub dest: String,
+    pub imported: usize,
+    pub bytes: u64,
+    pub skipped: usize,
+    pub duplicates: Vec<(String, String)>,
+    pub mismatched: Vec<String>,
+    pub failed: Vec<(String, String)>,
+}
 
 #[derive(thiserror::Error, Debug)]
 pub enum ImportError {
     #[error("io-error {0}: {1}")]
     Io(String, String),
+    #[error("verify-error: {0} files failed checksum verification")]
+    Verify(usize),
+    #[error("source-error: {0}")]
+    Source(String),
+    #[error("mirror-error: {0} files could not be written to the mirror")]
+    Mirror(usize),
+    #[error("space-error: need {0}, {1} available on destination (use --force to import what fits)")]
+    Space(String, String),
+}
+
+const COPY_ATTEMPTS: usize = 3;
+const COLLISIONS_FILE: &str = ".iphoto-collisions";
+
+/// Whether a planned destination name is free to use.
+pub enum Clash {
+    Free,
+    Same(String),
+    Taken(String),
+}
+
+pub enum Outcome {
+    Copied(u64),
+    Moved(u64),
+    Skipped,
+    Duplicate(String),
+    Mismatch,
+    Failed(String),
 }
 
 type E = ImportError;
@@ -102,123 +424,1505 @@ fn io_error(why: String, who: String) -> E {
     E::Io(why, who)
 }
 
+/// A destination library with its own journal and content index. The
+/// first target of a task is the primary; a mirror takes the names
+/// planned against it.
+pub struct Target {
+    pub root: PathBuf,
+    journal: Mutex<Journal>,
+    library: Mutex<Library>,
+}
+
+impl Target {
+    pub fn open(root: &Path) -> R<Target> {
+        let root_str = root.to_str().unwrap().to_string();
+        let journal = Journal::open(root).map_err(|_| io_error("journal".to_string(), root_str.clone()))?;
+        let library = Library::open(root).map_err(|_| io_error("index".to_string(), root_str.clone()))?;
+        say!("[INDEX] {} files in library {root_str}", library.len());
+        Ok(Target {
+            root: root.to_path_buf(),
+            journal: Mutex::new(journal),
+            library: Mutex::new(library),
+        })
+    }
+
+    fn dest(&self, item: &Item) -> PathBuf {
+        self.root.join(&item.key)
+    }
+
+    fn mark(&self, state: State, item: &Item, sum: Option<&str>) -> R<()> {
+        self.journal
+            .lock()
+            .unwrap()
+            .mark(state, &item.key, item.src.to_str().unwrap(), sum)
+            .map_err(|_| io_error("journal".to_string(), item.key.clone()))
+    }
+}
+
 pub struct Task<'a> {
     request: &'a mut Request,
+    targets: Vec<Target>,
+    /// Why the mirror could not be opened; every file fails there then.
+    lost: Option<String>,
+    /// Sources left off the mirror for want of space.
+    no_room: HashSet<PathBuf>,
+}
+
+/// One planned source file and where it is going.
+pub struct Item {
+    pub src: PathBuf,
+    pub info: fninfo::Info,
+    pub dest: String,
+    pub key: String,
+    pub size: u64,
+    /// The media file a sidecar belongs to.
+    pub primary: Option<PathBuf>,
+}
+
+impl Item {
+    /// The media file whose group this item is in: its primary, or itself.
+    fn group(&self) -> &Path {
+        self.primary.as_deref().unwrap_or(&self.src)
+    }
 }
 
-fn glob_ex(base_dir:&str) -> Vec<PathBuf> {
-    let patterns = vec!(
-        base_dir.to_owned() + "/*.ARW",
-        base_dir.to_owned() + "/*.NEF");
-    
-    return patterns.iter()
-        .map(|x| glob::glob(x))
-        .map(|x| x.unwrap()).flatten()
-        .map(|x| x.unwrap())
-        .collect();
+/// Creation time of `path`, or its modification time on filesystems that
+/// do not record one.
+fn created(path: &Path) -> std::io::Result<SystemTime> {
+    let meta = fs::metadata(path)?;
+    meta.created().or_else(|_| meta.modified())
+}
+
+fn is_hidden(e: &walkdir::DirEntry) -> bool {
+    e.file_name().to_str().is_some_and(|x| x.starts_with('.'))
+}
+
+/// Every file under the media folders of a card: DCIM and Sony's clip
+/// folder. A plain directory is only scanned one level deep.
+fn scan_source(src: &Path) -> Vec<PathBuf> {
+    let mut roots = Vec::new();
+    if src.ends_with("DCIM") {
+        roots.push(src.to_path_buf());
+    } else if src.join("DCIM").is_dir() {
+        roots.push(src.join("DCIM"));
+    }
+    let clip = src.join("PRIVATE/M4ROOT/CLIP");
+    if clip.is_dir() {
+        roots.push(clip);
+    }
+    let depth = if roots.is_empty() {
+        roots.push(src.to_path_buf());
+        1
+    } else {
+        usize::MAX
+    };
+
+    roots
+        .iter()
+        .flat_map(|root| {
+            WalkDir::new(root)
+                .max_depth(depth)
+                .sort_by_file_name()
+                .into_iter()
+                .filter_entry(|e| !is_hidden(e))
+                .flatten()
+        })
+        .filter(|e| e.file_type().is_file())
+        .map(|e| e.into_path())
+        .collect()
+}
+
+/// The media file a sidecar belongs to: same stem (`DSC0001.XMP`), Sony
+/// clip metadata (`C0001M01.XML`) or a double extension (`DSC0001.NEF.xmp`).
+fn sidecar_primary<'b>(
+    sidecar: &Path,
+    media: &'b HashMap<(PathBuf, String), PathBuf>,
+) -> Option<&'b PathBuf> {
+    let dir = sidecar.parent()?.to_path_buf();
+    let stem = sidecar.file_stem()?.to_str()?.to_ascii_uppercase();
+    let stems = [
+        Some(stem.as_str()),
+        stem.strip_suffix("M01"),
+        Path::new(&stem).file_stem().and_then(|x| x.to_str()),
+    ];
+    let primary = stems
+        .into_iter()
+        .flatten()
+        .find_map(|x| media.get(&(dir.clone(), x.to_string())));
+    primary
+}
+
+/// Run `f` over `items` on `jobs` threads, keeping the input order.
+fn par_map<T: Sync, U: Send>(jobs: usize, items: &[T], f: impl Fn(&T) -> U + Sync) -> Vec<U> {
+    let next = AtomicUsize::new(0);
+    let results = Mutex::new(Vec::with_capacity(items.len()));
+    thread::scope(|s| {
+        for _ in 0..jobs {
+            s.spawn(|| loop {
+                let i = next.fetch_add(1, Ordering::SeqCst);
+                if i >= items.len() {
+                    break;
+                }
+                let u = f(&items[i]);
+                results.lock().unwrap().push((i, u));
+            });
+        }
+    });
+    let mut results = results.into_inner().unwrap();
+    results.sort_by_key(|(i, _)| *i);
+    results.into_iter().map(|(_, u)| u).collect()
+}
+
+/// Insert `suffix` between the file stem and the extension of `path`.
+fn with_suffix(path: &str, suffix: &str) -> String {
+    let stem_start = path.rfind('/').map_or(0, |i| i + 1);
+    match path[stem_start..].rfind('.') {
+        Some(i) => {
+            let i = stem_start + i;
+            format!("{}{suffix}{}", &path[..i], &path[i..])
+        }
+        None => format!("{path}{suffix}"),
+    }
+}
+
+/// Flush a file or directory to stable storage.
+fn sync_path(path: &Path) -> std::io::Result<()> {
+    fs::File::open(path)?.sync_all()
+}
+
+fn cached_sum(sums: &mut HashMap<PathBuf, checksum::Sum>, path: &Path) -> R<checksum::Sum> {
+    if let Some(sum) = sums.get(path) {
+        return Ok(*sum);
+    }
+    let sum = checksum::file_sum(path)
+        .map_err(|_| io_error("hash".to_string(), path.to_str().unwrap().to_string()))?;
+    sums.insert(path.to_path_buf(), sum);
+    Ok(sum)
+}
+
+/// Copy `src` to `dest` and compare the source digest with a fresh read of
+/// the destination. A mismatching destination is removed before returning.
+fn copy_verified(src: &Path, dest: &Path, expected: u64) -> R<Option<(u64, String)>> {
+    let src_str = src.to_str().unwrap().to_string();
+    let (size, src_sum) = checksum::copy_with_sum(src, dest)
+        .map_err(|_| io_error("copy".to_string(), src_str.clone()))?;
+    let dest_sum = checksum::file_sum(dest)
+        .map_err(|_| io_error("verify".to_string(), src_str.clone()))?;
+    // a read that ends early matches its own truncated digest
+    if size == expected && src_sum == dest_sum {
+        return Ok(Some((size, checksum::to_hex(&src_sum))));
+    }
+    fs::remove_file(dest).map_err(|_| io_error("remove".to_string(), src_str))?;
+    Ok(None)
 }
 
 impl<'a> Task<'a> {
-    pub fn copy<S: AsRef<Path>>(&mut self, src: S, prog:&mut Progress) -> R<u64> {
-        let src = src.as_ref();
-        let _start = Instant::now();
+    /// Work out the destination of `src`. Sidecars take their name from
+    /// `primary`; `None` means no capture metadata could be read.
+    pub fn plan(&self, src: &Path, primary: Option<&Path>, seq: usize) -> R<Option<Item>> {
         let src_str = src.to_str().unwrap();
-        let dest_root_str = self.request.dest.to_str().unwrap().to_string();
 
-        let info = fninfo::from(src_str).unwrap();
-        let date_str = info.datetime[0..8].to_string();
+        let info = match primary {
+            Some(primary) => fninfo::from(primary.to_str().unwrap()).map(|info| fninfo::Info {
+                ext: src.extension().unwrap().to_str().unwrap().to_uppercase(),
+                ..info
+            }),
+            None => fninfo::from(src_str),
+        };
+        let Ok(mut info) = info else {
+            return Ok(None);
+        };
+        if self.request.template.needs_exif() {
+            info.fill_from_exif(primary.unwrap_or(src).to_str().unwrap());
+        }
+        let key = self.request.template.render(&info, seq);
+        let dest = self.request.dest.join(&key).to_str().unwrap().to_string();
+        let size = fs::metadata(src)
+            .map_err(|_| io_error("stat".to_string(), src_str.to_string()))?
+            .len();
 
-        let (dest_dir_str, dest_str) = if self.request.compact {
-            info.to_compact_dir_and_full(&dest_root_str)
-        } else {
-            info.to_dir_and_full(&dest_root_str)
+        Ok(Some(Item {
+            src: src.to_path_buf(),
+            info,
+            dest,
+            key,
+            size,
+            primary: primary.map(Path::to_path_buf),
+        }))
+    }
+
+    fn primary(&self) -> &Target {
+        &self.targets[0]
+    }
+
+    /// Look for the content of `item` anywhere in the library of `target`.
+    /// The source is only hashed when some library file has the same size.
+    fn find_duplicate(&self, target: &Target, item: &Item) -> R<Option<String>> {
+        let candidates = target.library.lock().unwrap().candidates(item.size);
+        if candidates.is_empty() {
+            return Ok(None);
+        }
+        let src_str = item.src.to_str().unwrap().to_string();
+        let src_sum = checksum::file_sum(&item.src)
+            .map_err(|_| io_error("hash".to_string(), src_str.clone()))?;
+        let src_sum = checksum::to_hex(&src_sum);
+        for (rel, sum) in candidates {
+            let sum = match sum {
+                Some(sum) => sum,
+                None => {
+                    let path = target.library.lock().unwrap().path(&rel);
+                    let sum = checksum::file_sum(&path)
+                        .map_err(|_| io_error("hash".to_string(), rel.clone()))?;
+                    let sum = checksum::to_hex(&sum);
+                    target.library.lock().unwrap().set_sum(&rel, sum.clone());
+                    sum
+                }
+            };
+            if sum == src_sum {
+                return Ok(Some(rel));
+            }
+        }
+        Ok(None)
+    }
+
+    fn suffixed(&self, item: Item, suffix: &str) -> Item {
+        Item {
+            dest: with_suffix(&item.dest, suffix),
+            key: with_suffix(&item.key, suffix),
+            ..item
+        }
+    }
+
+    /// Check whether the destination name is already taken, on disk or by
+    /// `planned`, a file planned earlier in this run. Different content
+    /// (DSC counter rollover, two bodies of one model) gets a suffix derived
+    /// from the source digest, so the choice is the same on every run.
+    fn collision(&self, item: &Item, planned: Option<&PathBuf>) -> R<Clash> {
+        let dest = Path::new(&item.dest);
+        if !dest.is_file() && planned.is_none() {
+            return Ok(Clash::Free);
+        }
+        let src_str = item.src.to_str().unwrap().to_string();
+        let src_sum = checksum::file_sum(&item.src)
+            .map_err(|_| io_error("hash".to_string(), src_str.clone()))?;
+        let src_sum = checksum::to_hex(&src_sum);
+        let suffix = format!("-{}", &src_sum[..6]);
+
+        if let Some(other) = planned {
+            let other_str = other.to_str().unwrap().to_string();
+            let other_sum = checksum::file_sum(other)
+                .map_err(|_| io_error("hash".to_string(), other_str.clone()))?;
+            if checksum::to_hex(&other_sum) == src_sum {
+                return Ok(Clash::Same(other_str));
+            }
+            return Ok(Clash::Taken(suffix));
+        }
+
+        let dest_size = fs::metadata(dest)
+            .map_err(|_| io_error("stat".to_string(), item.dest.clone()))?
+            .len();
+        if dest_size == item.size {
+            let cached = self.primary().library.lock().unwrap().cached_sum(&item.key);
+            let dest_sum = match cached {
+                Some(sum) => sum,
+                None => {
+                    let sum = checksum::file_sum(dest)
+                        .map_err(|_| io_error("hash".to_string(), item.dest.clone()))?;
+                    let sum = checksum::to_hex(&sum);
+                    self.primary().library.lock().unwrap().set_sum(&item.key, sum.clone());
+                    sum
+                }
+            };
+            if dest_sum == src_sum {
+                return Ok(Clash::Free);
+            }
+        }
+        Ok(Clash::Taken(suffix))
+    }
+
+    fn record_collision(&self, wanted: &str, item: &Item) -> R<()> {
+        let path = self.request.dest.join(COLLISIONS_FILE);
+        let line = format!("{wanted}\t{}\t{}\n", item.key, item.src.to_str().unwrap());
+        fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&path)
+            .and_then(|mut f| std::io::Write::write_all(&mut f, line.as_bytes()))
+            .map_err(|_| io_error("collisions".to_string(), COLLISIONS_FILE.to_string()))
+    }
+
+    fn source_of(&self, file: &Path) -> Option<usize> {
+        self.request
+            .sources
+            .iter()
+            .position(|x| file.starts_with(&x.path))
+    }
+
+    /// Source index, path on the card and size/mtime stamp of `file`.
+    fn card_rel(&self, file: &Path) -> Option<(usize, String, (u64, i64))> {
+        let i = self.source_of(file)?;
+        let rel = file.strip_prefix(&self.request.sources[i].path).ok()?;
+        let meta = fs::metadata(file).ok()?;
+        Some((i, rel.to_str()?.to_string(), cardstate::file_stamp(&meta)))
+    }
+
+    /// Merge the selection from several cards by content: each digest is
+    /// imported once, from the first card that has it. `known` are files
+    /// the card state already has; they are not imported again but still
+    /// count when telling which files are on only one card. Returns the
+    /// dropped copies keyed by the file kept in their place.
+    fn merge(
+        &self,
+        selected: &mut Vec<(PathBuf, Kind)>,
+        known: &[PathBuf],
+        resp: &mut Response,
+    ) -> R<HashMap<PathBuf, Vec<PathBuf>>> {
+        say!(
+            "[MERGE] hashing {} files from {} sources",
+            selected.len() + known.len(),
+            self.request.sources.len()
+        );
+        let files: Vec<&Path> = selected
+            .iter()
+            .map(|x| x.0.as_path())
+            .chain(known.iter().map(PathBuf::as_path))
+            .collect();
+        let sums = par_map(self.request.jobs, &files, |file| checksum::file_sum(file));
+
+        let mut groups: HashMap<checksum::Sum, Vec<usize>> = HashMap::new();
+        let mut order = Vec::new();
+        for (i, sum) in sums.into_iter().enumerate() {
+            let sum = sum.map_err(|_| io_error("hash".to_string(), files[i].to_str().unwrap().to_string()))?;
+            let group = groups.entry(sum).or_default();
+            if group.is_empty() {
+                order.push(sum);
+            }
+            group.push(i);
+        }
+
+        let mut keep = vec![false; selected.len()];
+        let mut merged: HashMap<PathBuf, Vec<PathBuf>> = HashMap::new();
+        for sum in &order {
+            let group = &groups[sum];
+            // selected files come first; a group of known files is done
+            let new: Vec<usize> = group.iter().copied().filter(|&i| i < selected.len()).collect();
+            let Some(&first) = new.first() else {
+                continue;
+            };
+            keep[first] = true;
+            let kept = selected[first].0.clone();
+            let dropped: Vec<PathBuf> = new[1..].iter().map(|&i| selected[i].0.clone()).collect();
+            resp.merged += dropped.len();
+
+            let mut cards: Vec<usize> = group.iter().filter_map(|&i| self.source_of(files[i])).collect();
+            cards.sort();
+            cards.dedup();
+            if cards.len() < self.request.sources.len() {
+                resp.single.extend(new.iter().map(|&i| selected[i].0.to_str().unwrap().to_string()));
+            }
+            merged.insert(kept, dropped);
+        }
+
+        let mut keep = keep.into_iter();
+        selected.retain(|_| keep.next().unwrap());
+        Ok(merged)
+    }
+
+    /// Compare what still has to be copied with the free space on every
+    /// destination. Only the primary can stop the import: with `force`, it
+    /// keeps the oldest shots that fit, each with its sidecars, and returns
+    /// the rest. What does not fit on a mirror is returned second, to fail
+    /// there alone.
+    fn preflight(&self, items: &mut Vec<Item>) -> R<(Vec<Item>, HashSet<PathBuf>)> {
+        let mut avail = Vec::with_capacity(self.targets.len());
+        for target in &self.targets {
+            let root_str = target.root.to_str().unwrap().to_string();
+            let free = utils::available_space(&target.root)
+                .map_err(|_| io_error("statvfs".to_string(), root_str))?;
+            avail.push(free);
+        }
+        self.fit(items, &avail)
+    }
+
+    /// `preflight` with `avail` bytes free on each target.
+    fn fit(&self, items: &mut Vec<Item>, avail: &[u64]) -> R<(Vec<Item>, HashSet<PathBuf>)> {
+        let need = |t: usize, items: &[Item]| -> u64 {
+            let target = &self.targets[t];
+            items.iter().filter(|x| !target.dest(x).is_file()).map(|x| x.size).sum()
+        };
+        // a sidecar goes with its media file or not at all
+        let fitting = |t: usize, items: &mut Vec<Item>| {
+            items.sort_by(|a, b| {
+                (a.info.timestamp(), a.group(), a.primary.is_some())
+                    .cmp(&(b.info.timestamp(), b.group(), b.primary.is_some()))
+            });
+            let (mut used, mut fit) = (0, 0);
+            for group in items.chunk_by(|a, b| a.group() == b.group()) {
+                used += need(t, group);
+                if used > avail[t] {
+                    break;
+                }
+                fit += group.len();
+            }
+            fit
         };
 
-        let dest_dir = Path::new(&dest_dir_str);
+        let mut rest = Vec::new();
+        let total = need(0, items);
+        if total > avail[0] {
+            if !self.request.force {
+                return Err(E::Space(utils::human_bytes(total), utils::human_bytes(avail[0])));
+            }
+            let fit = fitting(0, items);
+            rest = items.split_off(fit);
+            say!(
+                "[SPACE] need {}, {} available: importing {} files, leaving {}",
+                utils::human_bytes(total),
+                utils::human_bytes(avail[0]),
+                items.len(),
+                rest.len()
+            );
+        }
+
+        let mut no_room = HashSet::new();
+        for (t, target) in self.targets.iter().enumerate().skip(1) {
+            let total = need(t, items);
+            if total <= avail[t] {
+                continue;
+            }
+            let fit = fitting(t, items);
+            no_room.extend(items[fit..].iter().map(|x| x.src.clone()));
+            say!(
+                "[SPACE] mirror {} needs {}, {} available: leaving {} files off it",
+                target.root.to_str().unwrap(),
+                utils::human_bytes(total),
+                utils::human_bytes(avail[t]),
+                items.len() - fit
+            );
+        }
+        Ok((rest, no_room))
+    }
+
+    /// Drop destinations the previous run left half-written.
+    fn resume(&mut self) -> R<()> {
+        for target in &mut self.targets {
+            let journal = target.journal.get_mut().unwrap();
+            let partials = journal.partials();
+            let verified = journal.count(State::Verified);
+            if partials.is_empty() && verified == 0 {
+                continue;
+            }
+            let root_str = target.root.to_str().unwrap().to_string();
+            let removed = journal
+                .recover(&target.root)
+                .map_err(|_| io_error("recover".to_string(), root_str.clone()))?;
+            say!("[RESUME] {root_str}: {verified} verified, {removed} partial files removed");
+        }
+        Ok(())
+    }
+
+    /// What `target` already holds of `item`, without writing anything.
+    /// `None` when it has to be copied.
+    fn check(&self, target: &Target, item: &Item) -> R<Option<Outcome>> {
+        let dest = target.dest(item);
+        let dest_str = dest.to_str().unwrap().to_string();
+
+        // partial files were removed by resume(); anything else is complete
+        let state = target.journal.lock().unwrap().state(&item.key);
+        if dest.is_file() && state != Some(State::Copying) {
+            // names were checked against the primary; a mirror may differ
+            let size = fs::metadata(&dest)
+                .map_err(|_| io_error("stat".to_string(), dest_str.clone()))?
+                .len();
+            if size != item.size {
+                return Err(io_error("exists".to_string(), dest_str));
+            }
+            return Ok(Some(Outcome::Skipped));
+        }
+
+        if let Some(existing) = self.find_duplicate(target, item)? {
+            return Ok(Some(Outcome::Duplicate(existing)));
+        }
+        Ok(None)
+    }
+
+    /// Get `target` ready to receive `item`. `Some` when nothing has to be
+    /// copied there.
+    fn prepare(&self, target: &Target, item: &Item) -> R<Option<Outcome>> {
+        if let Some(outcome) = self.check(target, item)? {
+            return Ok(Some(outcome));
+        }
+        let dest = target.dest(item);
+        let date_str = item.info.to_date();
+        let dest_dir = dest.parent().unwrap();
         if !dest_dir.is_dir() {
+            let dest_dir_str = dest_dir.to_str().unwrap().to_string();
             fs::create_dir_all(dest_dir)
                 .map_err(|_| io_error("create-dir".to_string(), dest_dir_str.clone()))?;
             if self.request.touch {
-                crate::core::touch::touch_form_0(&dest_dir_str, &date_str).unwrap();
+                crate::core::touch::touch_form_0(&dest_dir_str, &date_str, &self.request.tz)
+                    .map_err(|_| io_error("touch".to_string(), dest_dir_str.clone()))?;
             }
         }
-        let dest = Path::new(&dest_str);
-        if !dest.is_file() {
-            let r = if !self.request.rename {
-                fs::copy(src, &dest)
-            } else {
-                fs::rename(src, &dest).map(|_| 0)
-            };
 
-            // println!("SRC->{src_str}");
-            if self.request.touch && !self.request.rename {
-                let metadata = fs::metadata(&src_str).unwrap();
-                crate::core::touch::touch(&dest_str, metadata.created().unwrap()).unwrap();
-            }
-            
-            prog.inc();
-            // println!(
-            //     "{}/{} {src_str} -> {dest_str}  _  {:.2}s",
-            //     prog.cur, prog.total,
-            //     start.elapsed().as_secs_f32(),
-            // );
-            r.map_err(|_| io_error("copy".to_string(), src_str.to_string()))
+        target.mark(State::Copying, item, None)?;
+        Ok(None)
+    }
+
+    /// Verify what was written to `target` against the source digest,
+    /// copying again on a mismatch, and record the file once it matches.
+    /// `src_sum` is `None` when the source read came up short.
+    fn settle(
+        &self,
+        target: &Target,
+        item: &Item,
+        written: std::io::Result<()>,
+        src_sum: Option<&checksum::Sum>,
+    ) -> R<Outcome> {
+        let src = item.src.as_path();
+        let src_str = src.to_str().unwrap();
+        let dest = target.dest(item);
+        let dest_str = dest.to_str().unwrap().to_string();
+        written.map_err(|_| io_error("copy".to_string(), dest_str.clone()))?;
+
+        let dest_sum = checksum::file_sum(&dest)
+            .map_err(|_| io_error("verify".to_string(), dest_str.clone()))?;
+        let mut copied = None;
+        if let Some(src_sum) = src_sum.filter(|x| **x == dest_sum) {
+            copied = Some(checksum::to_hex(src_sum));
         } else {
-            prog.inc();
-            // println!("{src_str} -> {dest_str}  _  skip");
-            Ok(999999999)
+            fs::remove_file(&dest).map_err(|_| io_error("remove".to_string(), dest_str.clone()))?;
+        }
+        for _ in 1..COPY_ATTEMPTS {
+            if copied.is_some() {
+                break;
+            }
+            copied = copy_verified(src, &dest, item.size)?.map(|(_, sum)| sum);
+        }
+
+        let Some(sum) = copied else {
+            target.mark(State::Pending, item, None)?;
+            return Ok(Outcome::Mismatch);
+        };
+
+        if self.request.touch {
+            let time = created(src).map_err(|_| io_error("stat".to_string(), src_str.to_string()))?;
+            crate::core::touch::touch(&dest_str, time)
+                .map_err(|_| io_error("touch".to_string(), dest_str.clone()))?;
+        }
+        target
+            .library
+            .lock()
+            .unwrap()
+            .insert(&item.key, sum.clone())
+            .map_err(|_| io_error("index".to_string(), item.key.clone()))?;
+        if self.request.rename {
+            let dest_dir = dest.parent().unwrap();
+            sync_path(&dest).map_err(|_| io_error("fsync".to_string(), dest_str.clone()))?;
+            sync_path(dest_dir)
+                .map_err(|_| io_error("fsync".to_string(), dest_dir.to_str().unwrap().to_string()))?;
+        }
+        target.mark(State::Verified, item, Some(&sum))?;
+        Ok(Outcome::Copied(item.size))
+    }
+
+    /// Copy `item` to every target, reading the source once. The outcomes
+    /// follow the order of the targets; an error on a mirror becomes
+    /// `Failed` there instead of stopping the import.
+    pub fn copy(&self, item: &Item) -> R<Vec<Outcome>> {
+        let src = item.src.as_path();
+        let _start = Instant::now();
+        let src_str = src.to_str().unwrap();
+        let contain = |t: usize, e: E| match t {
+            0 => Err(e),
+            _ => Ok(Outcome::Failed(e.to_string())),
+        };
+
+        let mut outcomes = Vec::with_capacity(self.targets.len());
+        for (t, target) in self.targets.iter().enumerate() {
+            if t > 0 && self.no_room.contains(&item.src) {
+                outcomes.push(Some(Outcome::Failed("no space left".to_string())));
+                continue;
+            }
+            outcomes.push(match self.prepare(target, item) {
+                Ok(outcome) => outcome,
+                Err(e) => Some(contain(t, e)?),
+            });
+        }
+
+        let pending: Vec<usize> = (0..outcomes.len()).filter(|&t| outcomes[t].is_none()).collect();
+        if !pending.is_empty() {
+            let dests: Vec<PathBuf> = pending.iter().map(|&t| self.targets[t].dest(item)).collect();
+            let dests: Vec<&Path> = dests.iter().map(|x| x.as_path()).collect();
+            let (read, src_sum, written) = checksum::copy_to_all(src, &dests)
+                .map_err(|_| io_error("copy".to_string(), src_str.to_string()))?;
+            let src_sum = (read == item.size).then_some(&src_sum);
+            for (&t, written) in pending.iter().zip(written) {
+                let outcome = self.settle(&self.targets[t], item, written, src_sum);
+                outcomes[t] = Some(outcome.or_else(|e| contain(t, e))?);
+            }
+        }
+        let mut outcomes: Vec<Outcome> = outcomes.into_iter().flatten().collect();
+        if let Some(why) = &self.lost {
+            outcomes.push(Outcome::Failed(why.clone()));
+        }
+
+        // the source goes only once every destination holds a copy
+        let held = self.lost.is_none() && self.targets.iter().zip(&outcomes).all(|(t, x)| self.holds(t, item, x));
+        if self.request.rename && held {
+            fs::remove_file(src).map_err(|_| io_error("remove-source".to_string(), src_str.to_string()))?;
+            if let Outcome::Copied(size) = outcomes[0] {
+                outcomes[0] = Outcome::Moved(size);
+            }
+        }
+        // println!(
+        //     "{}/{} {src_str} -> {dest_str}  _  {:.2}s",
+        //     prog.cur, prog.total,
+        //     start.elapsed().as_secs_f32(),
+        // );
+        Ok(outcomes)
+    }
+
+    /// Whether `outcome` leaves a verified copy of `item` on `target`. A
+    /// file already in place counts once the journal or its digest says
+    /// so, which finishes a move interrupted before the source was removed.
+    fn holds(&self, target: &Target, item: &Item, outcome: &Outcome) -> bool {
+        match outcome {
+            Outcome::Copied(_) | Outcome::Duplicate(_) => true,
+            Outcome::Skipped => {
+                target.journal.lock().unwrap().state(&item.key) == Some(State::Verified)
+                    || matches!(
+                        (checksum::file_sum(target.dest(item)), checksum::file_sum(&item.src)),
+                        (Ok(dest), Ok(src)) if dest == src
+                    )
+            }
+            _ => false,
         }
     }
 
+    /// The steps `copy` would take for `items`, in order, with nothing
+    /// written. A destination directory is created once, by its first file;
+    /// content copied earlier in the plan counts as in the library.
+    fn dry_run(&self, items: &[Item]) -> R<Vec<Step>> {
+        let mut steps = Vec::new();
+        let mut made = HashSet::new();
+        let mut copies: HashMap<u64, Vec<&Item>> = HashMap::new();
+        let mut sums = HashMap::new();
+        for item in items {
+            let src_str = item.src.to_str().unwrap().to_string();
+            let mut earlier = None;
+            if let Some(others) = copies.get(&item.size) {
+                let sum = cached_sum(&mut sums, &item.src)?;
+                for other in others {
+                    if cached_sum(&mut sums, &other.src)? == sum {
+                        earlier = Some(other.key.as_str());
+                        break;
+                    }
+                }
+            }
+            let mut copied = Vec::with_capacity(self.targets.len());
+            for (t, target) in self.targets.iter().enumerate() {
+                let dest = target.dest(item);
+                let dest_str = dest.to_str().unwrap().to_string();
+                if t > 0 && self.no_room.contains(&item.src) {
+                    steps.push(Step::Fail { dest: dest_str, why: "no space left".to_string() });
+                    copied.push(false);
+                    continue;
+                }
+                let check = match self.check(target, item) {
+                    Err(e) if t > 0 => {
+                        steps.push(Step::Fail { dest: dest_str, why: e.to_string() });
+                        copied.push(false);
+                        continue;
+                    }
+                    Ok(None) => earlier.map(|key| Outcome::Duplicate(key.to_string())),
+                    check => check?,
+                };
+                match check {
+                    Some(Outcome::Duplicate(existing)) => {
+                        let existing = target.root.join(existing).to_str().unwrap().to_string();
+                        steps.push(Step::Duplicate { src: src_str.clone(), existing });
+                        copied.push(true);
+                    }
+                    Some(outcome) => {
+                        steps.push(Step::Skip { src: src_str.clone(), dest: dest_str });
+                        copied.push(self.request.rename && self.holds(target, item, &outcome));
+                    }
+                    None => {
+                        let dest_dir = dest.parent().unwrap();
+                        if !dest_dir.is_dir() && made.insert(dest_dir.to_path_buf()) {
+                            let path = dest_dir.to_str().unwrap().to_string();
+                            steps.push(Step::Mkdir { path: path.clone() });
+                            if self.request.touch {
+                                let midnight = self
+                                    .request
+                                    .tz
+                                    .instant(item.info.date().and_hms_opt(0, 0, 0).unwrap())
+                                    .ok_or_else(|| io_error("date".to_string(), src_str.clone()))?;
+                                let time = local_time(midnight.into());
+                                steps.push(Step::Touch { path, time });
+                            }
+                        }
+                        steps.push(Step::Copy {
+                            src: src_str.clone(),
+                            dest: dest_str.clone(),
+                            size: item.size,
+                        });
+                        if self.request.touch {
+                            let created = created(&item.src)
+                                .map_err(|_| io_error("stat".to_string(), src_str.clone()))?;
+                            steps.push(Step::Touch { path: dest_str, time: local_time(created) });
+                        }
+                        copied.push(true);
+                    }
+                }
+            }
+            if let (Some(why), Some(mirror)) = (&self.lost, &self.request.mirror) {
+                let dest = mirror.join(&item.key).to_str().unwrap().to_string();
+                steps.push(Step::Fail { dest, why: why.clone() });
+                copied.push(false);
+            }
+            if earlier.is_none() && copied.first() == Some(&true) {
+                copies.entry(item.size).or_default().push(item);
+            }
+            // as in copy(): the primary copies it, every mirror holds it
+            if self.request.rename && copied.iter().all(|x| *x) {
+                steps.push(Step::Remove { src: src_str });
+            }
+        }
+        Ok(steps)
+    }
+
     pub fn run(&mut self) -> Result<Response, ImportError> {
-        let src = &self.request.source;
-        let src_dir = src.to_str().unwrap();
+        let mut cards = Vec::with_capacity(self.request.sources.len());
+        for source in &self.request.sources {
+            cards.push(match &source.card {
+                Some(id) => Some(
+                    CardState::load(&self.request.dest, id)
+                        .map_err(|_| io_error("card-state".to_string(), id.clone()))?,
+                ),
+                None => None,
+            });
+        }
 
-        let src_pattern = if src_dir.ends_with("/DCIM") {
-            format!("{}/**", src_dir)
-        } else if src.join("DCIM").is_dir() {
-            format!("{}/DCIM/**", src_dir)
-        } else {
-            format!("{}", src_dir)
+        let mut resp = Response {
+            mirror: self.request.mirror.as_ref().map(|x| MirrorResponse {
+                dest: x.to_str().unwrap().to_string(),
+                ..Default::default()
+            }),
+            ..Default::default()
         };
-    
-        let src_dir = src_dir.to_string();
+        let mut media = HashMap::new();
+        let mut selected = Vec::new();
+        let mut known_files = Vec::new();
+        for (i, source) in self.request.sources.iter().enumerate() {
+            let files = scan_source(&source.path);
+            say!(
+                "[IMPORT] {} to {}, {} files",
+                source.path.to_str().unwrap(),
+                self.request.dest.to_str().unwrap(),
+                files.len()
+            );
 
-        let files = glob_ex(src_pattern.as_str());
+            let before = selected.len();
+            let mut known = 0;
+            for file in files {
+                match self.request.kind(&file) {
+                    Ok(kind) => {
+                        if let Kind::Media = kind {
+                            let stem = file.file_stem().unwrap().to_str().unwrap().to_ascii_uppercase();
+                            let dir = file.parent().unwrap().to_path_buf();
+                            media.entry((dir, stem)).or_insert_with(|| file.clone());
+                        }
+                        if let (Some(state), Some((_, rel, stamp))) = (&cards[i], self.card_rel(&file)) {
+                            if state.contains(&rel, stamp) {
+                                known += 1;
+                                known_files.push(file);
+                                continue;
+                            }
+                        }
+                        selected.push((file, kind));
+                    }
+                    Err(why) => resp.ignored.push((file.to_str().unwrap().to_string(), why)),
+                }
+            }
+            if let (Some(id), Some(state)) = (&source.card, &cards[i]) {
+                if state.is_empty() {
+                    say!("[CARD] {id}: first import from this card");
+                } else {
+                    say!(
+                        "[CARD] {id}: {} new since last import on this card, {known} already imported",
+                        selected.len() - before
+                    );
+                }
+            }
+        }
+        let merged = if self.request.sources.len() > 1 {
+            self.merge(&mut selected, &known_files, &mut resp)?
+        } else {
+            HashMap::new()
+        };
 
+        if !self.request.dry {
+            self.resume()?;
+        }
+        // sidecars follow the collision suffix of their media file
+        selected.sort_by_key(|(_, kind)| matches!(kind, Kind::Sidecar));
+        let mut suffixes: HashMap<PathBuf, String> = HashMap::new();
+        let mut planned: HashMap<String, PathBuf> = HashMap::new();
+        let mut seqs: HashMap<PathBuf, usize> = HashMap::new();
+        let mut items = Vec::with_capacity(selected.len());
+        for (file, kind) in &selected {
+            let primary = match kind {
+                Kind::Media => None,
+                Kind::Sidecar => match sidecar_primary(file, &media) {
+                    Some(primary) => Some(primary.as_path()),
+                    None => {
+                        resp.ignored.push((file.to_str().unwrap().to_string(), "orphan-sidecar"));
+                        continue;
+                    }
+                },
+            };
+            // {seq} counts media files; a sidecar shares its primary's number
+            let seq = match primary {
+                Some(primary) => seqs.get(primary).copied().unwrap_or(0),
+                None => seqs.len() + 1,
+            };
+            let Some(item) = self.plan(file, primary, seq)? else {
+                resp.ignored.push((file.to_str().unwrap().to_string(), "no-meta"));
+                continue;
+            };
+            if !self.request.filter.matches(&item.info) {
+                resp.filtered += 1;
+                continue;
+            }
+            if primary.is_none() {
+                seqs.insert(file.clone(), seq);
+            }
+            let item = match primary.and_then(|p| suffixes.get(p)) {
+                Some(suffix) => self.suffixed(item, suffix),
+                None => item,
+            };
+            let item = match self.collision(&item, planned.get(&item.key))? {
+                Clash::Same(other) => {
+                    resp.duplicates.push((item.src.to_str().unwrap().to_string(), other));
+                    continue;
+                }
+                Clash::Taken(suffix) => {
+                    let wanted = item.key.clone();
+                    let item = self.suffixed(item, &suffix);
+                    if !Path::new(&item.dest).is_file() {
+                        if !self.request.dry {
+                            self.record_collision(&wanted, &item)?;
+                        }
+                        resp.collisions
+                            .push((item.src.to_str().unwrap().to_string(), item.key.clone()));
+                    }
+                    suffixes.insert(item.src.clone(), suffix);
+                    item
+                }
+                Clash::Free => item,
+            };
+            planned.insert(item.key.clone(), item.src.clone());
+            for target in self.targets.iter().filter(|_| !self.request.dry) {
+                if target.journal.lock().unwrap().state(&item.key).is_none() {
+                    target.mark(State::Pending, &item, None)?;
+                }
+            }
+            items.push(item);
+        }
+        let (rest, no_room) = self.preflight(&mut items)?;
+        for item in rest {
+            resp.ignored.push((item.src.to_str().unwrap().to_string(), "no-space"));
+        }
+        self.no_room = no_room;
+        if self.request.dry {
+            resp.plan = self.dry_run(&items)?;
+            for (file, why) in &resp.ignored {
+                say!("[IGNORED] {why} {file}");
+            }
+            return Ok(resp);
+        }
 
-        println!(
-            "[IMPORT] {} to {}, {} photos",
-            src_dir,
-            self.request.dest.to_str().unwrap(),
-            files.len()
-        );
+        let total_bytes = items.iter().map(|x| x.size).sum();
+        let mut prog = Progress::with_bytes(items.len(), total_bytes);
 
-        
-        let mut prog = Progress::new(files.len());
-        
         term::init(stderr().is_terminal());
         term::hide_cursor().unwrap();
 
         let mut pb = tqdm!(
-            total = prog.total,
+            total = prog.total_bytes as usize,
             desc = "import ",
             animation = "classic",
+            unit = "B",
+            unit_scale = true,
+            unit_divisor = 1024,
+            postfix = format!("0/{} files", prog.total),
             // position = 6,
             force_refresh = true
         );
-        
-        for file in &files {
-            self.copy(file, &mut prog)?;
-            pb.update_to(prog.cur).unwrap();
-        }
+
+        let mut error = None;
+        let next = AtomicUsize::new(0);
+        let abort = AtomicBool::new(false);
+        let (tx, rx) = mpsc::channel();
+        let task = &*self;
+        let items = &items;
+        thread::scope(|s| {
+            for _ in 0..task.request.jobs {
+                let tx = tx.clone();
+                let (next, abort) = (&next, &abort);
+                s.spawn(move || loop {
+                    let i = next.fetch_add(1, Ordering::SeqCst);
+                    if i >= items.len() || abort.load(Ordering::SeqCst) {
+                        break;
+                    }
+                    let r = task.copy(&items[i]);
+                    if r.is_err() {
+                        abort.store(true, Ordering::SeqCst);
+                    }
+                    if tx.send((i, r)).is_err() {
+                        break;
+                    }
+                });
+            }
+            drop(tx);
+
+            for (i, r) in rx {
+                let item = &items[i];
+                let src_str = item.src.to_str().unwrap().to_string();
+                let done = r.as_ref().is_ok_and(|outcomes| {
+                    outcomes.iter().all(|x| {
+                        matches!(
+                            x,
+                            Outcome::Copied(_) | Outcome::Moved(_) | Outcome::Skipped | Outcome::Duplicate(_)
+                        )
+                    })
+                });
+                if done {
+                    let copies = merged.get(&item.src).into_iter().flatten();
+                    for file in std::iter::once(&item.src).chain(copies) {
+                        if let Some((i, rel, stamp)) = task.card_rel(file) {
+                            if let Some(state) = &mut cards[i] {
+                                state.insert(&rel, stamp);
+                            }
+                        }
+                    }
+                }
+                let mut outcomes = match r {
+                    Ok(outcomes) => outcomes.into_iter(),
+                    Err(e) => {
+                        error.get_or_insert(e);
+                        Vec::new().into_iter()
+                    }
+                };
+                match outcomes.next() {
+                    Some(Outcome::Copied(size)) => {
+                        resp.imported += 1;
+                        resp.bytes += size;
+                    }
+                    Some(Outcome::Moved(size)) => {
+                        resp.imported += 1;
+                        resp.moved += 1;
+                        resp.bytes += size;
+                    }
+                    Some(Outcome::Skipped) => resp.skipped += 1,
+                    Some(Outcome::Duplicate(existing)) => resp.duplicates.push((src_str.clone(), existing)),
+                    Some(Outcome::Mismatch) => resp.mismatched.push(src_str.clone()),
+                    Some(Outcome::Failed(_)) | None => (),
+                }
+                if let (Some(mirror), Some(outcome)) = (&mut resp.mirror, outcomes.next()) {
+                    match outcome {
+                        Outcome::Copied(size) | Outcome::Moved(size) => {
+                            mirror.imported += 1;
+                            mirror.bytes += size;
+                        }
+                        Outcome::Skipped => mirror.skipped += 1,
+                        Outcome::Duplicate(existing) => mirror.duplicates.push((src_str, existing)),
+                        Outcome::Mismatch => mirror.mismatched.push(src_str),
+                        Outcome::Failed(why) => mirror.failed.push((src_str, why)),
+                    }
+                }
+                prog.add(item.size);
+                pb.set_postfix(format!("{}/{} files", prog.cur, prog.total));
+                pb.update_to(prog.bytes as usize).unwrap();
+            }
+        });
         term::show_cursor().unwrap();
-        Ok(Response {})
+        eprintln!();
+
+        for state in cards.iter().flatten() {
+            state
+                .save()
+                .map_err(|_| io_error("card-state".to_string(), cardstate::CARDS_DIR.to_string()))?;
+        }
+
+        if let Some(e) = error {
+            return Err(e);
+        }
+
+        say!(
+            "[IMPORT] {} imported ({} moved), {} skipped, {} filtered, {} duplicates, {} mismatched, {} ignored",
+            resp.imported,
+            resp.moved,
+            resp.skipped,
+            resp.filtered,
+            resp.duplicates.len(),
+            resp.mismatched.len(),
+            resp.ignored.len()
+        );
+        if let Some(mirror) = &resp.mirror {
+            say!(
+                "[MIRROR] {}: {} imported, {} skipped, {} duplicates, {} mismatched, {} failed",
+                mirror.dest,
+                mirror.imported,
+                mirror.skipped,
+                mirror.duplicates.len(),
+                mirror.mismatched.len(),
+                mirror.failed.len()
+            );
+        }
+        if self.request.sources.len() > 1 {
+            say!(
+                "[MERGE] {} copies merged, {} files found on only one card",
+                resp.merged,
+                resp.single.len()
+            );
+        }
+        for file in &resp.single {
+            say!("[SINGLE] {file}");
+        }
+        for (file, dest) in &resp.collisions {
+            say!("[COLLISION] {file} -> {dest}");
+        }
+        for (file, why) in &resp.ignored {
+            say!("[IGNORED] {why} {file}");
+        }
+        for (file, existing) in &resp.duplicates {
+            say!("[DUPLICATE] {file} = {existing}");
+        }
+        for file in &resp.mismatched {
+            say!("[MISMATCH] {file}");
+        }
+        if let Some(mirror) = &resp.mirror {
+            for (file, existing) in &mirror.duplicates {
+                say!("[MIRROR] duplicate {file} = {existing}");
+            }
+            for file in &mirror.mismatched {
+                say!("[MIRROR] mismatch {file}");
+            }
+            for (file, why) in &mirror.failed {
+                say!("[MIRROR] failed {file}: {why}");
+            }
+        }
+        Ok(resp)
     }
 }
 
 pub fn do_import(request: &mut Request) -> Result<Response, ImportError> {
     // println!("THIS IS import ACTION");
-    let mut task = Task { request };
-    task.run()
+    let mut targets = vec![Target::open(&request.dest)?];
+    // a mirror that cannot be opened fails on its own, as a full one does
+    let mut lost = None;
+    if let Some(mirror) = &request.mirror {
+        match Target::open(mirror) {
+            Ok(target) => targets.push(target),
+            Err(e) => {
+                say!("[MIRROR] {e}: importing without the mirror");
+                lost = Some(e.to_string());
+            }
+        }
+    }
+    let mut task = Task {
+        request,
+        targets,
+        lost,
+        no_room: HashSet::new(),
+    };
+    let resp = task.run();
+    if task.request.dry {
+        return resp;
+    }
+    for target in &mut task.targets {
+        target
+            .library
+            .get_mut()
+            .unwrap()
+            .save()
+            .map_err(|_| io_error("index".to_string(), INDEX_FILE.to_string()))?;
+    }
+    let resp = resp?;
+    for target in task.targets {
+        target
+            .journal
+            .into_inner()
+            .unwrap()
+            .finish()
+            .map_err(|_| io_error("journal".to_string(), JOURNAL_FILE.to_string()))?;
+    }
+    Ok(resp)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::bmff::mk;
+
+    /// A minimal MP4 from an ILCE-1: mvhd v0 `created` (Unix seconds, UTC),
+    /// 10 s long, with `frames` as the media data.
+    fn mp4(created: u32, frames: &[u8]) -> Vec<u8> {
+        let mut mvhd = vec![0u8; 4];
+        mvhd.extend((created + 2_082_844_800).to_be_bytes());
+        mvhd.extend([0, 0, 0, 0, 0, 0, 3, 232, 0, 0, 39, 16]);
+        let model = [&[0, 6, 0, 0][..], b"ILCE-1"].concat();
+        let moov = mk(b"moov", &[mk(b"mvhd", &mvhd), mk(b"udta", &mk(b"\xa9mod", &model))].concat());
+        [mk(b"ftyp", b"mp42\0\0\0\0"), mk(b"mdat", frames), moov].concat()
+    }
+
+    fn request(src: &Path, dest: &Path) -> Request {
+        Request {
+            sources: vec![Source {
+                path: src.to_path_buf(),
+                card: None,
+            }],
+            dest: dest.to_path_buf(),
+            template: Template::parse(template::DEFAULT).unwrap(),
+            touch: false,
+            rename: false,
+            jobs: 1,
+            include: Vec::new(),
+            exclude: Vec::new(),
+            force: false,
+            mirror: None,
+            dry: false,
+            tz: Zone::Local,
+            filter: Filter::default(),
+        }
+    }
+
+    #[test]
+    fn test_import_video() {
+        let dir = std::env::temp_dir().join(format!("iphoto-import-video-{}", std::process::id()));
+        let (card, dest) = (dir.join("card"), dir.join("dest"));
+        fs::create_dir_all(card.join("DCIM/100MSDCF")).unwrap();
+
+        let created = 1672898468u32;
+        fs::write(card.join("DCIM/100MSDCF/C0001.MP4"), mp4(created, b"frames")).unwrap();
+
+        let resp = do_import(&mut request(&card, &dest)).unwrap();
+        assert_eq!((resp.imported, resp.ignored.len()), (1, 0));
+        let local = chrono::DateTime::from_timestamp(created.into(), 0).unwrap().with_timezone(&chrono::Local);
+        let name = format!("{}/{}__00001__A1.MP4", local.format("%Y%m%d"), local.format("%Y%m%d_%H%M%S"));
+        assert!(dest.join(&name).is_file(), "{name} missing");
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_import_same_stem() {
+        let dir = std::env::temp_dir().join(format!("iphoto-import-stem-{}", std::process::id()));
+        let (card, dest) = (dir.join("card"), dir.join("dest"));
+        // one clip number in three folders: two different clips, one copy
+        let created = 1672898468u32;
+        for (folder, frames) in [("100MSDCF", "first"), ("101MSDCF", "second"), ("102MSDCF", "first")] {
+            fs::create_dir_all(card.join("DCIM").join(folder)).unwrap();
+            fs::write(card.join("DCIM").join(folder).join("C0001.MP4"), mp4(created, frames.as_bytes())).unwrap();
+        }
+
+        let resp = do_import(&mut request(&card, &dest)).unwrap();
+        assert_eq!((resp.imported, resp.duplicates.len(), resp.collisions.len()), (2, 1, 1));
+        let local = chrono::DateTime::from_timestamp(created.into(), 0).unwrap().with_timezone(&chrono::Local);
+        let name = format!("{}/{}__00001__A1.MP4", local.format("%Y%m%d"), local.format("%Y%m%d_%H%M%S"));
+        let second = checksum::to_hex(&checksum::file_sum(card.join("DCIM/101MSDCF/C0001.MP4")).unwrap());
+        let suffixed = with_suffix(&name, &format!("-{}", &second[..6]));
+        assert_eq!(fs::read(dest.join(&name)).unwrap(), fs::read(card.join("DCIM/100MSDCF/C0001.MP4")).unwrap());
+        assert_eq!(fs::read(dest.join(&suffixed)).unwrap(), fs::read(card.join("DCIM/101MSDCF/C0001.MP4")).unwrap());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_short_read_is_mismatch() {
+        let dir = std::env::temp_dir().join(format!("iphoto-import-short-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let (src, dest) = (dir.join("DSC00001.ARW"), dir.join("copy.ARW"));
+        fs::write(&src, b"truncated").unwrap();
+
+        // the card listed 12 bytes, the read gave 9
+        assert!(copy_verified(&src, &dest, 12).unwrap().is_none());
+        assert!(!dest.exists());
+        assert_eq!(copy_verified(&src, &dest, 9).unwrap().unwrap().0, 9);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_par_map_keeps_order() {
+        let items: Vec<u64> = (0..64).collect();
+        // later items finish first
+        let out = par_map(8, &items, |x| {
+            thread::sleep(std::time::Duration::from_micros(64 - x));
+            x * 2
+        });
+        assert_eq!(out, items.iter().map(|x| x * 2).collect::<Vec<_>>());
+        assert_eq!(par_map(3, &[] as &[u64], |x| *x), Vec::<u64>::new());
+    }
+
+    #[test]
+    fn test_import_jobs() {
+        let dir = std::env::temp_dir().join(format!("iphoto-import-jobs-{}", std::process::id()));
+        let (card, dest) = (dir.join("card"), dir.join("dest"));
+        fs::create_dir_all(card.join("DCIM/100MSDCF")).unwrap();
+        let mut size = 0;
+        for i in 1..=12u32 {
+            let clip = mp4(1672898468 + i * 60, format!("clip {i}").as_bytes());
+            size += clip.len() as u64;
+            fs::write(card.join(format!("DCIM/100MSDCF/C{i:04}.MP4")), clip).unwrap();
+        }
+
+        let mut req = request(&card, &dest);
+        req.jobs = 4;
+        let resp = do_import(&mut req).unwrap();
+        assert_eq!((resp.imported, resp.bytes, resp.ignored.len()), (12, size, 0));
+        let copied = WalkDir::new(&dest)
+            .into_iter()
+            .flatten()
+            .filter(|e| e.file_type().is_file() && e.path().extension().is_some_and(|x| x == "MP4"))
+            .count();
+        assert_eq!(copied, 12);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_filter_matches() {
+        let info = |name: &str| fninfo::Info::from_path(name).unwrap();
+        let (first, last) = (info("20230105_235959__00001__A1.ARW"), info("20230107_000000__00002__A1.ARW"));
+        // a body alias from the config is matched by name like a model
+        let second = info("20230106_120000__00003__A1-2.ARW");
+        let day = |s: &str| NaiveDate::parse_from_str(s, "%Y%m%d").ok();
+
+        assert!(Filter::default().matches(&first));
+        let range = Filter {
+            since: day("20230105"),
+            until: day("20230106"),
+            ..Default::default()
+        };
+        assert!(range.matches(&first) && range.matches(&second) && !range.matches(&last));
+        let since = Filter {
+            since: day("20230106"),
+            ..Default::default()
+        };
+        assert!(!since.matches(&first) && since.matches(&last));
+
+        let models = Filter {
+            models: vec!["a1".to_string()],
+            ..Default::default()
+        };
+        assert!(models.matches(&first) && !models.matches(&second));
+        let alias = Filter {
+            models: vec!["a1-2".to_string(), "A7R4A".to_string()],
+            until: day("20230106"),
+            ..Default::default()
+        };
+        assert!(alias.matches(&second) && !alias.matches(&first));
+    }
+
+    #[test]
+    fn test_fit_keeps_sidecars() {
+        let dir = std::env::temp_dir().join(format!("iphoto-import-fit-{}", std::process::id()));
+        let (card, dest) = (dir.join("card"), dir.join("dest"));
+        let item = |name: &str, size: u64, primary: Option<&str>| {
+            let key = format!("20230105/{name}");
+            Item {
+                src: card.join(name),
+                info: fninfo::Info::from_path(name).unwrap(),
+                dest: dest.join(&key).to_str().unwrap().to_string(),
+                key,
+                size,
+                primary: primary.map(|x| card.join(x)),
+            }
+        };
+        let (a, b) = ("20230105_150108__00001__A1.ARW", "20230105_150109__00002__A1.ARW");
+        let items = || {
+            vec![
+                item("20230105_150110__00003__A1.JPG", 50, None),
+                item("20230105_150109__00002__A1.XMP", 10, Some(b)),
+                item(b, 100, None),
+                item("20230105_150108__00001__A1.XMP", 10, Some(a)),
+                item(a, 100, None),
+            ]
+        };
+
+        let mut req = request(&card, &dest);
+        let task = Task {
+            targets: vec![Target::open(&dest).unwrap(), Target::open(&dir.join("mirror")).unwrap()],
+            request: &mut req,
+            lost: None,
+            no_room: HashSet::new(),
+        };
+        let (rest, no_room) = task.fit(&mut items(), &[270, 270]).unwrap();
+        assert!(rest.is_empty() && no_room.is_empty());
+        assert!(matches!(task.fit(&mut items(), &[215, 270]), Err(E::Space(..))));
+
+        // the oldest pair fits; the next one would not with its sidecar
+        task.request.force = true;
+        let mut kept = items();
+        let (rest, _) = task.fit(&mut kept, &[215, 270]).unwrap();
+        let names = |x: &[Item]| x.iter().map(|x| x.key[9..].to_string()).collect::<Vec<_>>();
+        assert_eq!(names(&kept), [a, "20230105_150108__00001__A1.XMP"]);
+        assert_eq!(names(&rest), [b, "20230105_150109__00002__A1.XMP", "20230105_150110__00003__A1.JPG"]);
+
+        // a full mirror leaves the primary whole, with or without force
+        task.request.force = false;
+        let mut kept = items();
+        let (rest, no_room) = task.fit(&mut kept, &[270, 215]).unwrap();
+        assert_eq!((kept.len(), rest.len()), (5, 0));
+        let mut left: Vec<&Path> = no_room.iter().map(|x| x.as_path()).collect();
+        left.sort();
+        let off = [b, "20230105_150109__00002__A1.XMP", "20230105_150110__00003__A1.JPG"];
+        assert_eq!(left, off.map(|x| card.join(x)));
+        fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn test_move_removes_verified_sources() {
+        let dir = std::env::temp_dir().join(format!("iphoto-import-move-{}", std::process::id()));
+        let (card, dest) = (dir.join("card"), dir.join("dest"));
+        fs::create_dir_all(&card).unwrap();
+        let item = |name: &str, size: u64| {
+            fs::write(card.join(name), name).unwrap();
+            let key = format!("20230105/{name}");
+            Item {
+                src: card.join(name),
+                info: fninfo::Info::from_path(name).unwrap(),
+                dest: dest.join(&key).to_str().unwrap().to_string(),
+                key,
+                size,
+                primary: None,
+            }
+        };
+        let mut req = request(&card, &dest);
+        req.rename = true;
+        let task = Task {
+            targets: vec![Target::open(&dest).unwrap()],
+            request: &mut req,
+            lost: None,
+            no_room: HashSet::new(),
+        };
+
+        let moved = item("20230105_150108__00001__A1.ARW", 30);
+        assert!(matches!(task.copy(&moved).unwrap()[..], [Outcome::Moved(30)]));
+        assert!(!moved.src.exists());
+        assert_eq!(fs::read_to_string(dest.join(&moved.key)).unwrap(), "20230105_150108__00001__A1.ARW");
+
+        // a move stopped after the copy was verified is finished on re-run
+        let stopped = item("20230105_150109__00002__A1.ARW", 30);
+        fs::copy(&stopped.src, dest.join(&stopped.key)).unwrap();
+        assert!(matches!(task.copy(&stopped).unwrap()[..], [Outcome::Skipped]));
+        assert!(!stopped.src.exists());
+
+        // the card listed more than can be read: never verified, kept
+        let short = item("20230105_150110__00003__A1.ARW", 31);
+        assert!(matches!(task.copy(&short).unwrap()[..], [Outcome::Mismatch]));
+        assert!(short.src.is_file());
+        assert!(!dest.join(&short.key).exists());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_merge_two_cards() {
+        let dir = std::env::temp_dir().join(format!("iphoto-import-merge-{}", std::process::id()));
+        let slots = [dir.join("slot1"), dir.join("slot2")];
+        // both slots record C0001 and C0002; slot 1 filled up before C0003
+        for (i, slot) in slots.iter().enumerate() {
+            fs::create_dir_all(slot.join("DCIM/100MSDCF")).unwrap();
+            for n in 1..=(2 + i as u32) {
+                let clip = mp4(1672898468 + n * 60, format!("clip {n}").as_bytes());
+                fs::write(slot.join(format!("DCIM/100MSDCF/C{n:04}.MP4")), clip).unwrap();
+            }
+        }
+        let single = slots[1].join("DCIM/100MSDCF/C0003.MP4").to_str().unwrap().to_string();
+        let both = |dest: &Path| {
+            let mut req = request(&slots[0], dest);
+            req.sources = slots
+                .iter()
+                .enumerate()
+                .map(|(i, x)| Source {
+                    path: x.clone(),
+                    card: Some(format!("slot{i}")),
+                })
+                .collect();
+            req
+        };
+
+        let resp = do_import(&mut both(&dir.join("fresh"))).unwrap();
+        assert_eq!((resp.imported, resp.merged), (3, 2));
+        assert_eq!(resp.single, std::slice::from_ref(&single));
+
+        // slot 1 on its own first: its files are known, not missing, after
+        let dest = dir.join("again");
+        let mut req = request(&slots[0], &dest);
+        req.sources[0].card = Some("slot0".to_string());
+        assert_eq!(do_import(&mut req).unwrap().imported, 2);
+        let resp = do_import(&mut both(&dest)).unwrap();
+        assert_eq!(resp.imported, 1);
+        assert_eq!(resp.single, [single]);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_lost_mirror() {
+        let dir = std::env::temp_dir().join(format!("iphoto-import-lost-{}", std::process::id()));
+        let (card, dest, mirror) = (dir.join("card"), dir.join("dest"), dir.join("mirror"));
+        fs::create_dir_all(card.join("DCIM/100MSDCF")).unwrap();
+        fs::write(card.join("DCIM/100MSDCF/C0001.MP4"), mp4(1672898468, b"frames")).unwrap();
+        // its index cannot be read: the mirror is a file
+        fs::write(&mirror, "").unwrap();
+
+        let mut req = request(&card, &dest);
+        req.mirror = Some(mirror);
+        req.rename = true;
+        let resp = do_import(&mut req).unwrap();
+        assert_eq!((resp.imported, resp.moved), (1, 0));
+        assert_eq!(resp.mirror.unwrap().failed.len(), 1);
+        assert!(card.join("DCIM/100MSDCF/C0001.MP4").is_file());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_dry_run_writes_nothing() {
+        let dir = std::env::temp_dir().join(format!("iphoto-import-dry-{}", std::process::id()));
+        let (card, dest) = (dir.join("card"), dir.join("dest"));
+        let created = 1672898468u32;
+        // one clip number in two folders, so a collision is planned too
+        for folder in ["100MSDCF", "101MSDCF"] {
+            fs::create_dir_all(card.join("DCIM").join(folder)).unwrap();
+            fs::write(card.join("DCIM").join(folder).join("C0001.MP4"), mp4(created, folder.as_bytes())).unwrap();
+        }
+
+        let mut req = request(&card, &dest);
+        req.sources[0].card = Some("0123abcd".to_string());
+        req.rename = true;
+        req.dry = true;
+        let resp = do_import(&mut req).unwrap();
+        assert_eq!((resp.imported, resp.collisions.len(), resp.plan.len()), (0, 1, 5));
+
+        let local = chrono::DateTime::from_timestamp(created.into(), 0).unwrap().with_timezone(&chrono::Local);
+        let day = dest.join(local.format("%Y%m%d").to_string());
+        let name = format!("{}__00001__A1.MP4", local.format("%Y%m%d_%H%M%S"));
+        let src = card.join("DCIM/100MSDCF/C0001.MP4");
+        let json = serde_json::to_value(&resp.plan[..3]).unwrap();
+        assert_eq!(
+            json,
+            serde_json::json!([
+                {"action": "mkdir", "path": day},
+                {"action": "copy", "src": src, "dest": day.join(name), "size": fs::metadata(&src).unwrap().len()},
+                {"action": "remove", "src": src},
+            ])
+        );
+        assert!(matches!(&resp.plan[3..], [Step::Copy { .. }, Step::Remove { .. }]));
+
+        assert!(src.is_file() && !day.exists());
+        for file in [JOURNAL_FILE, COLLISIONS_FILE, cardstate::CARDS_DIR] {
+            assert!(!dest.join(file).exists(), "{file} written");
+        }
+        fs::remove_dir_all(&dir).unwrap();
+    }
 }
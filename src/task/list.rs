@@ -0,0 +1,201 @@
+use std::collections::BTreeMap;
+use std::path::Path;
+
+use clap::{Parser, ValueEnum};
+use serde::Serialize;
+use walkdir::WalkDir;
+
+use crate::cmd::{Cmd, CmdResult};
+use crate::core::exposure::Exposure;
+use crate::core::fninfo::Info;
+use crate::core::utils;
+
+// ==== COMMAND ====
+#[derive(Clone, Copy, Debug, ValueEnum)]
+pub enum Field {
+    Date,
+    Model,
+    Lens,
+    Focal,
+    Aperture,
+    Shutter,
+    Iso,
+}
+
+#[derive(Parser, Debug)]
+pub struct ListCommand {
+    #[arg(default_value = ".")]
+    dir: String,
+    #[arg(help = "print a JSON record per line instead of tab-separated columns.")]
+    #[arg(long, default_value_t = false)]
+    json: bool,
+    #[arg(help = "count files by this field instead of listing them.")]
+    #[arg(long, value_enum)]
+    by: Option<Field>,
+}
+
+impl Cmd for ListCommand {
+    fn run(self) -> CmdResult {
+        let rows = do_list(Path::new(&self.dir));
+        if let Some(field) = self.by {
+            for (count, value) in count_by(&rows, field) {
+                println!("{count}\t{value}");
+            }
+        } else if self.json {
+            for row in &rows {
+                println!("{}", serde_json::to_string(row)?);
+            }
+        } else {
+            println!("path\tdatetime\tsource\tmodel\tserial\tlens\tfocal\taperture\tshutter\tiso");
+            for row in &rows {
+                let e = &row.exposure;
+                let cols = [
+                    Some(row.path.clone()),
+                    Some(row.datetime.clone()),
+                    Some(row.source.clone()),
+                    Some(row.model.clone()),
+                    row.serial.clone(),
+                    e.lens.clone(),
+                    e.focal_text(),
+                    e.aperture_text(),
+                    e.shutter_text(),
+                    e.iso_text(),
+                ];
+                let cols: Vec<String> = cols.into_iter().map(|x| x.unwrap_or("-".to_string())).collect();
+                println!("{}", cols.join("\t"));
+            }
+        }
+        Ok(())
+    }
+}
+
+// ==== TASK ====
+/// What is known about one file, as listed.
+#[derive(Serialize)]
+pub struct Row {
+    pub path: String,
+    pub datetime: String,
+    /// `20230105`, for counting by day.
+    #[serde(skip)]
+    pub date: String,
+    pub millis: Option<u16>,
+    pub offset: Option<String>,
+    pub source: String,
+    pub model: String,
+    pub serial: Option<String>,
+    /// Seconds, for videos.
+    pub duration: Option<f64>,
+    pub exposure: Exposure,
+}
+
+impl Row {
+    fn from(path: &str, info: Info) -> Row {
+        Row {
+            path: path.to_string(),
+            datetime: info.to_datetime(),
+            date: info.to_date(),
+            millis: info.millis,
+            offset: info.offset.map(|x| x.to_string()),
+            source: info.source.to_string(),
+            model: info.model,
+            serial: info.serial,
+            duration: info.duration.map(|x| x.as_secs_f64()),
+            exposure: info.exposure,
+        }
+    }
+
+    fn field(&self, field: Field) -> Option<String> {
+        let e = &self.exposure;
+        match field {
+            Field::Date => Some(self.date.clone()),
+            Field::Model => Some(self.model.clone()),
+            Field::Lens => e.lens.clone(),
+            Field::Focal => e.focal_text(),
+            Field::Aperture => e.aperture_text(),
+            Field::Shutter => e.shutter_text(),
+            Field::Iso => e.iso_text(),
+        }
+    }
+}
+
+/// Files per value of `field`, most first; files without one count as `-`.
+pub fn count_by(rows: &[Row], field: Field) -> Vec<(usize, String)> {
+    let mut counts: BTreeMap<String, usize> = BTreeMap::new();
+    for row in rows {
+        *counts.entry(row.field(field).unwrap_or("-".to_string())).or_default() += 1;
+    }
+    let mut counts: Vec<(usize, String)> = counts.into_iter().map(|(k, v)| (v, k)).collect();
+    counts.sort_by(|a, b| b.0.cmp(&a.0).then_with(|| a.1.cmp(&b.1)));
+    counts
+}
+
+/// Photos and videos under `dir`, read as import would read them, with
+/// what only EXIF has filled in for files already named.
+pub fn do_list(dir: &Path) -> Vec<Row> {
+    let walker = WalkDir::new(dir)
+        .sort_by_file_name()
+        .into_iter()
+        .filter_entry(|e| e.depth() == 0 || !e.file_name().to_string_lossy().starts_with('.'));
+    let mut rows = Vec::new();
+    for e in walker.flatten() {
+        let ext = e.path().extension().map(|x| x.to_string_lossy().to_lowercase());
+        if !e.file_type().is_file() || !ext.is_some_and(|x| utils::is_img_ext(&x) || utils::is_video_ext(&x)) {
+            continue;
+        }
+        let Some(path) = e.path().to_str() else {
+            eprintln!("[LIST] skip {}: path is not UTF-8", e.path().display());
+            continue;
+        };
+        match Info::from(path) {
+            Ok(mut info) => {
+                info.fill_from_exif(path);
+                rows.push(Row::from(path, info));
+            }
+            Err(err) => eprintln!("[LIST] {err}"),
+        }
+    }
+    rows
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn row(date: &str, model: &str, lens: Option<&str>) -> Row {
+        Row {
+            path: String::new(),
+            datetime: format!("{date}_150108"),
+            date: date.to_string(),
+            millis: None,
+            offset: None,
+            source: String::new(),
+            model: model.to_string(),
+            serial: None,
+            duration: None,
+            exposure: Exposure {
+                lens: lens.map(str::to_string),
+                ..Default::default()
+            },
+        }
+    }
+
+    #[test]
+    fn test_count_by() {
+        let rows = [
+            row("20230105", "A7R4A", None),
+            row("20230105", "A1", Some("FE 24-70mm F2.8 GM II")),
+            row("20230106", "A1", Some("FE 24-70mm F2.8 GM II")),
+            row("20230106", "A1", None),
+            row("20230107", "A7R4A", Some("FE 35mm F1.4 GM")),
+        ];
+        let counts = |field| count_by(&rows, field);
+        let pair = |n, s: &str| (n, s.to_string());
+        assert_eq!(counts(Field::Model), [pair(3, "A1"), pair(2, "A7R4A")]);
+        assert_eq!(counts(Field::Date), [pair(2, "20230105"), pair(2, "20230106"), pair(1, "20230107")]);
+        assert_eq!(
+            counts(Field::Lens),
+            [pair(2, "-"), pair(2, "FE 24-70mm F2.8 GM II"), pair(1, "FE 35mm F1.4 GM")]
+        );
+        assert_eq!(counts(Field::Iso), [pair(5, "-")]);
+    }
+}
@@ -0,0 +1,336 @@
+use std::collections::{BTreeMap, HashSet};
+use std::fs::{self, File};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use clap::Parser;
+use walkdir::WalkDir;
+
+use crate::cmd::{Cmd, CmdResult};
+use crate::core::fninfo::{Info, InfoVer};
+use crate::core::library::{Library, INDEX_FILE};
+use crate::core::utils;
+
+/// Mapping files are `.iphoto-migrate-<time>.tsv` in the migrated root.
+const MAP_PREFIX: &str = ".iphoto-migrate-";
+
+// ==== COMMAND ====
+#[derive(Parser, Debug)]
+pub struct MigrateCommand {
+    #[arg(default_value = ".")]
+    #[arg(help = "library whose V1 and compact names are renamed to V2.")]
+    dir: String,
+    #[arg(short, long, default_value_t = false)]
+    #[arg(help = "show what would have been renamed")]
+    dry: bool,
+    #[arg(help = "rename back what an earlier run recorded in this mapping file.")]
+    #[arg(long)]
+    undo: Option<PathBuf>,
+}
+
+impl Cmd for MigrateCommand {
+    fn run(self) -> CmdResult {
+        let root = Path::new(&self.dir);
+        let resp = match &self.undo {
+            Some(map) => do_undo(root, map, self.dry)?,
+            None => do_migrate(root, self.dry)?,
+        };
+        println!(
+            "[MIGRATE] {} files and {} sidecars {}, {} skipped",
+            resp.renamed,
+            resp.sidecars,
+            if self.dry { "to rename" } else { "renamed" },
+            resp.skipped
+        );
+        if let Some(map) = resp.map {
+            println!("[MIGRATE] mapping in {}, undo with --undo", map.display());
+        }
+        Ok(())
+    }
+}
+
+// ==== TASK ====
+#[derive(thiserror::Error, Debug)]
+pub enum MigrateError {
+    #[error("io-error {0}: {1}")]
+    Io(String, String),
+    #[error("map-error: bad line {0} in {1}")]
+    Map(usize, String),
+}
+
+type R<T> = Result<T, MigrateError>;
+
+fn io_error(op: &str, path: &Path, e: std::io::Error) -> MigrateError {
+    MigrateError::Io(op.to_string(), format!("{}: {e}", path.display()))
+}
+
+/// One file to rename, both paths relative to the root.
+pub struct Rename {
+    pub from: String,
+    pub to: String,
+    pub sidecar: bool,
+}
+
+#[derive(Default)]
+pub struct Response {
+    pub renamed: usize,
+    pub sidecars: usize,
+    pub skipped: usize,
+    /// Mapping file written by this run.
+    pub map: Option<PathBuf>,
+}
+
+/// V2 name of an old-style file, in the same directory. Milliseconds in
+/// a compact name are kept whatever the config says, so nothing is lost.
+fn v2_name(rel: &str) -> Option<String> {
+    let info = Info::from_path(rel)?;
+    if !matches!(info.ver, InfoVer::V1 | InfoVer::Compact) {
+        return None;
+    }
+    let millis = info.millis.map(|x| format!("_{x:03}")).unwrap_or_default();
+    let name = format!(
+        "{}{}__{}__{}.{}",
+        info.to_datetime(),
+        millis,
+        info.number,
+        info.model,
+        info.ext
+    );
+    let dir = Path::new(rel).parent().unwrap_or(Path::new(""));
+    Some(dir.join(name).to_string_lossy().to_string())
+}
+
+/// A sidecar of `from`, such as `X.XMP` or `X.ARW.xmp` for `X.ARW`,
+/// renamed to follow `to`.
+fn sidecar_name(name: &str, from: &Path, to: &Path) -> Option<String> {
+    let old = from.file_stem()?.to_str()?;
+    let new = to.file_stem()?.to_str()?;
+    let rest = name.strip_prefix(old)?.strip_prefix('.')?;
+    let ext = Path::new(name).extension()?.to_str()?;
+    utils::is_sidecar_ext(ext.to_lowercase()).then(|| format!("{new}.{rest}"))
+}
+
+/// Files under `root` by directory, hidden ones left out.
+fn scan(root: &Path) -> R<BTreeMap<PathBuf, Vec<String>>> {
+    let mut dirs: BTreeMap<PathBuf, Vec<String>> = BTreeMap::new();
+    let walker = WalkDir::new(root)
+        .min_depth(1)
+        .sort_by_file_name()
+        .into_iter()
+        .filter_entry(|e| !e.file_name().to_string_lossy().starts_with('.'));
+    for e in walker {
+        let e = e.map_err(|e| MigrateError::Io("walkdir".to_string(), e.to_string()))?;
+        if !e.file_type().is_file() {
+            continue;
+        }
+        let rel = e.path().strip_prefix(root).unwrap();
+        let dir = rel.parent().unwrap_or(Path::new("")).to_path_buf();
+        dirs.entry(dir).or_default().push(e.file_name().to_string_lossy().to_string());
+    }
+    Ok(dirs)
+}
+
+/// Renames for every media file under `root` with a V1 or compact name,
+/// each followed by those of its sidecars. A sidecar shared by a RAW+JPEG
+/// pair goes with the first of them. A group whose target is taken is left
+/// out and counted in `skipped`.
+pub fn plan(root: &Path) -> R<(Vec<Rename>, usize)> {
+    let mut renames = Vec::new();
+    let mut taken = HashSet::new();
+    let mut claimed = HashSet::new();
+    let mut skipped = 0;
+    for (dir, names) in scan(root)? {
+        for name in &names {
+            let ext = Path::new(name).extension().map(|x| x.to_string_lossy().to_lowercase());
+            if !ext.is_some_and(|x| utils::is_img_ext(&x) || utils::is_video_ext(&x)) {
+                continue;
+            }
+            let from = dir.join(name);
+            let Some(to) = v2_name(&from.to_string_lossy()) else {
+                continue;
+            };
+            let to = PathBuf::from(to);
+            let mut group = vec![Rename {
+                from: from.to_string_lossy().to_string(),
+                to: to.to_string_lossy().to_string(),
+                sidecar: false,
+            }];
+            for other in names.iter().filter(|x| *x != name && !claimed.contains(&dir.join(x))) {
+                if let Some(new) = sidecar_name(other, &from, &to) {
+                    group.push(Rename {
+                        from: dir.join(other).to_string_lossy().to_string(),
+                        to: dir.join(new).to_string_lossy().to_string(),
+                        sidecar: true,
+                    });
+                }
+            }
+            if let Some(x) = group.iter().find(|x| taken.contains(&x.to) || root.join(&x.to).exists()) {
+                println!("[MIGRATE] skip {}: {} exists", x.from, x.to);
+                skipped += 1;
+                continue;
+            }
+            taken.extend(group.iter().map(|x| x.to.clone()));
+            claimed.extend(group.iter().map(|x| PathBuf::from(&x.from)));
+            renames.extend(group);
+        }
+    }
+    Ok((renames, skipped))
+}
+
+/// Apply `renames` under `root`, recording each in the mapping file as it
+/// happens so that an interrupted run can still be undone.
+fn apply(root: &Path, renames: &[Rename], map: Option<&Path>) -> R<Response> {
+    let mut library = match root.join(INDEX_FILE).is_file() {
+        true => Some(Library::open(root).map_err(|e| io_error("index", root, e))?),
+        false => None,
+    };
+    let mut log = match map {
+        Some(map) => Some(File::create(map).map_err(|e| io_error("create", map, e))?),
+        None => None,
+    };
+    let mut resp = Response {
+        map: map.map(Path::to_path_buf),
+        ..Default::default()
+    };
+    let mut result = Ok(());
+    for x in renames {
+        let (from, to) = (root.join(&x.from), root.join(&x.to));
+        if let Err(e) = fs::rename(&from, &to) {
+            result = Err(io_error("rename", &from, e));
+            break;
+        }
+        if let Some(library) = library.as_mut() {
+            library.rename(&x.from, &x.to);
+        }
+        if let Some(log) = log.as_mut() {
+            if let Err(e) = writeln!(log, "{}\t{}", x.from, x.to).and_then(|_| log.flush()) {
+                result = Err(io_error("write", map.unwrap(), e));
+                break;
+            }
+        }
+        println!("[MIGRATE] {} -> {}", x.from, x.to);
+        if x.sidecar {
+            resp.sidecars += 1;
+        } else {
+            resp.renamed += 1;
+        }
+    }
+    // the index follows what was renamed, also when a rename failed
+    if let Some(library) = library {
+        library.save().map_err(|e| io_error("index", root, e))?;
+    }
+    result.map(|_| resp)
+}
+
+fn preview(renames: &[Rename], skipped: usize) -> Response {
+    for x in renames {
+        println!("[MIGRATE] {} -> {}", x.from, x.to);
+    }
+    Response {
+        renamed: renames.iter().filter(|x| !x.sidecar).count(),
+        sidecars: renames.iter().filter(|x| x.sidecar).count(),
+        skipped,
+        map: None,
+    }
+}
+
+pub fn do_migrate(root: &Path, dry: bool) -> R<Response> {
+    let (renames, skipped) = plan(root)?;
+    if dry {
+        return Ok(preview(&renames, skipped));
+    }
+    if renames.is_empty() {
+        return Ok(Response { skipped, ..Default::default() });
+    }
+    let time = chrono::Local::now().format("%Y%m%d_%H%M%S");
+    let map = root.join(format!("{MAP_PREFIX}{time}.tsv"));
+    let resp = apply(root, &renames, Some(&map))?;
+    Ok(Response { skipped, ..resp })
+}
+
+/// Rename back, newest first, what `map` records. Files moved or
+/// replaced since are skipped.
+pub fn do_undo(root: &Path, map: &Path, dry: bool) -> R<Response> {
+    let text = fs::read_to_string(map).map_err(|e| io_error("read", map, e))?;
+    let mut renames = Vec::new();
+    let mut skipped = 0;
+    let lines: Vec<&str> = text.lines().collect();
+    for (i, line) in lines.iter().enumerate().rev() {
+        let Some((from, to)) = line.split_once('\t') else {
+            return Err(MigrateError::Map(i + 1, map.display().to_string()));
+        };
+        if !root.join(to).exists() || root.join(from).exists() {
+            println!("[MIGRATE] skip {to}: not where {} left it", map.display());
+            skipped += 1;
+            continue;
+        }
+        let ext = Path::new(from).extension().map(|x| x.to_string_lossy().to_lowercase());
+        renames.push(Rename {
+            from: to.to_string(),
+            to: from.to_string(),
+            sidecar: ext.is_some_and(utils::is_sidecar_ext),
+        });
+    }
+    if dry {
+        return Ok(preview(&renames, skipped));
+    }
+    let resp = apply(root, &renames, None)?;
+    Ok(Response { skipped, ..resp })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_v2_name() {
+        assert_eq!(
+            v2_name("2023/A_02104__20230105_150108.arw").as_deref(),
+            Some("2023/20230105_150108__02104__A7R4A.ARW")
+        );
+        assert_eq!(
+            v2_name("20230105/150108_120__03212__A1.ARW").as_deref(),
+            Some("20230105/20230105_150108_120__03212__A1.ARW")
+        );
+        assert_eq!(v2_name("20230105/20230105_150108__03212__A1.ARW"), None);
+
+        let (from, to) = (Path::new("A_02104__20230105_150108.ARW"), Path::new("20230105_150108__02104__A7R4A.ARW"));
+        assert_eq!(
+            sidecar_name("A_02104__20230105_150108.ARW.xmp", from, to).as_deref(),
+            Some("20230105_150108__02104__A7R4A.ARW.xmp")
+        );
+        assert_eq!(sidecar_name("A_02104__20230105_150108.JPG", from, to), None);
+    }
+
+    #[test]
+    fn test_migrate_and_undo() {
+        let root = std::env::temp_dir().join(format!("iphoto-migrate-{}", std::process::id()));
+        fs::create_dir_all(root.join("2023")).unwrap();
+        // a RAW+JPEG pair sharing one sidecar, and a file whose V2 name is taken
+        let old = [
+            "2023/A_02104__20230105_150108.ARW",
+            "2023/A_02104__20230105_150108.JPG",
+            "2023/A_02104__20230105_150108.XMP",
+            "2023/A_02105__20230105_150109.ARW",
+            "2023/20230105_150109__02105__A7R4A.ARW",
+        ];
+        for name in old {
+            fs::write(root.join(name), name).unwrap();
+        }
+
+        let resp = do_migrate(&root, false).unwrap();
+        assert_eq!((resp.renamed, resp.sidecars, resp.skipped), (2, 1, 1));
+        let xmp = root.join("2023/20230105_150108__02104__A7R4A.XMP");
+        assert_eq!(fs::read_to_string(xmp).unwrap(), old[2]);
+        assert!(root.join("2023/20230105_150108__02104__A7R4A.ARW").is_file());
+        assert!(root.join("2023/20230105_150108__02104__A7R4A.JPG").is_file());
+        assert_eq!(fs::read_to_string(root.join(old[3])).unwrap(), old[3]);
+
+        let resp = do_undo(&root, &resp.map.unwrap(), false).unwrap();
+        assert_eq!((resp.renamed, resp.sidecars, resp.skipped), (2, 1, 0));
+        for name in old {
+            assert_eq!(fs::read_to_string(root.join(name)).unwrap(), name);
+        }
+        fs::remove_dir_all(&root).unwrap();
+    }
+}
@@ -1,4 +1,7 @@
+pub mod clock;
 pub mod import;
+pub mod list;
+pub mod migrate;
 pub mod rename;
 pub mod rename2;
 pub mod tidyup;
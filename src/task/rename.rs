@@ -1,9 +1,11 @@
-use std::{collections::HashMap, path::Path};
+use std::{cell::Cell, collections::HashMap, path::Path};
 
 use clap::Parser;
 
 use crate::cmd::{Cmd, CmdResult};
 use crate::core::fninfo::Info;
+use crate::core::template::{self, Template, TemplateError};
+use crate::core::zone::{self, Zone};
 use crate::core::scandir::{scan as scan_dir, DirEntry};
 
 // ==== COMMAND ====
@@ -20,15 +22,22 @@ pub struct RenameCommand {
     #[arg(short, long, default_value_t = false)]
     #[arg(help = "rename in compact one mode")]
     compact: bool,
+    #[arg(help = "naming layout; only its file name part is used, e.g. {time}__{number}__{model}.{ext}.")]
+    #[arg(long)]
+    template: Option<String>,
     #[arg(help = "disable touch file timestamp.")]
     #[arg(short, long = "no-touch", default_value_t = true)]
     #[arg(action=clap::ArgAction::SetFalse)]
     touch: bool,
+    #[arg(help = "time zone of capture times without a recorded offset: local, utc, +09:00, Asia/Tokyo.")]
+    #[arg(long, default_value = "local", value_parser = zone::parse)]
+    tz: Zone,
 }
 
 impl Cmd for RenameCommand {
     fn run(self) -> CmdResult {
-        do_rename(&Request::from(&self))?;
+        zone::set(self.tz);
+        do_rename(&Request::from(&self)?)?;
         Ok(())
     }
 }
@@ -59,17 +68,26 @@ pub struct Request {
     pub dry: bool,
     pub compact: bool,
     pub touch: bool,
+    pub tz: Zone,
+    /// File name layout without the extension.
+    pub template: Template,
+    pub seq: Cell<usize>,
 }
 
 impl Request {
-    pub fn from(cmd: &RenameCommand) -> Self {
-        Request {
+    pub fn from(cmd: &RenameCommand) -> Result<Self, TemplateError> {
+        let default = if cmd.compact { template::COMPACT } else { template::DEFAULT };
+        let template = Template::resolve(cmd.template.as_deref(), default)?.file_stem();
+        Ok(Request {
             dir: cmd.dir.clone(),
             exif: cmd.exif,
             dry: cmd.dry,
             compact: cmd.compact,
             touch: cmd.touch,
-        }
+            tz: cmd.tz,
+            template,
+            seq: Cell::new(0),
+        })
     }
 }
 
@@ -136,19 +154,17 @@ fn build_rename_map(
         }
 
         let meta = meta.unwrap();
-        let meta = if req.exif {
+        let mut meta = if req.exif {
             meta.update_from_exif(&full_path)
         } else {
             meta
         };
+        if req.template.needs_exif() {
+            meta.fill_from_exif(&full_path);
+        }
 
-        let meta_name = meta.to_name();
-
-        let meta_name = if req.compact {
-            meta_name[9..].to_string()
-        } else {
-            meta_name
-        };
+        req.seq.set(req.seq.get() + 1);
+        let meta_name = req.template.render(&meta, req.seq.get());
 
         if !file_stem.eq(&meta_name) {
             println!("{level} - {full_path:?} -> {}.{}", meta_name, file_ext);
@@ -173,7 +189,7 @@ fn do_rename_files(
     files: &Vec<DirEntry>,
     map: &HashMap<String, RenameEntry>,
 ) {
-    if map.len() == 0 || files.len() == 0 {
+    if map.is_empty() || files.is_empty() {
         return;
     }
 
@@ -189,16 +205,13 @@ fn do_rename_files(
         }
         let file_ext = file_ext.unwrap().to_str().unwrap();
 
-        match map.get(&file_stem) {
-            Some(r) => {
-                let name = &r.name;
-                let new_fn = format!("{base_dir}/{name}.{file_ext}");
-                println!("RENAME {file_path} -> {new_fn}");
-                if !req.dry {
-                    rename(req, file_path, &new_fn, &r.meta).expect("do_rename_faile");
-                }
+        if let Some(r) = map.get(&file_stem) {
+            let name = &r.name;
+            let new_fn = format!("{base_dir}/{name}.{file_ext}");
+            println!("RENAME {file_path} -> {new_fn}");
+            if !req.dry {
+                rename(req, file_path, &new_fn, &r.meta).expect("do_rename_faile");
             }
-            None => (),
         }
     }
 }
@@ -206,7 +219,10 @@ fn do_rename_files(
 fn rename(req: &Request, src: &str, dest: &str, meta: &Info) -> Result<(), std::io::Error> {
     std::fs::rename(src, dest)?;
     if !req.compact && req.touch {
-        crate::core::touch::touch(dest, meta.to_systemtime())?
+        match meta.to_systemtime(&req.tz) {
+            Some(time) => crate::core::touch::touch(dest, time)?,
+            None => println!("TOUCH {dest}: {} does not exist in --tz, mtime left", meta.to_datetime()),
+        }
     };
     Ok(())
 }
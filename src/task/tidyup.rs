@@ -1,8 +1,10 @@
-use std::{path::Path, time::Instant};
+use std::{cell::Cell, path::Path, time::Instant};
 
 use crate::core::{
     scandir::{scan as scan_dir, DirEntry},
+    template::{self, Template},
     utils,
+    zone::{self, Zone},
 };
 
 use clap::{ArgAction, Parser};
@@ -31,11 +33,29 @@ pub struct TidyupCommand {
     #[arg(short, long = "no-year", default_value_t = true)]
     #[arg(action=ArgAction::SetFalse)]
     year: bool,
+    #[arg(help = "naming layout, e.g. {year}/{date}/{time}__{number}__{model}.{ext}.")]
+    #[arg(long)]
+    template: Option<String>,
+    #[arg(help = "time zone of capture times without a recorded offset: local, utc, +09:00, Asia/Tokyo.")]
+    #[arg(long, default_value = "local", value_parser = zone::parse)]
+    tz: Zone,
 }
 
 impl Cmd for TidyupCommand {
     fn run(self) -> CmdResult {
-        let mut task = Task { cmd: self };
+        zone::set(self.tz);
+        let default = match (self.year, self.compact) {
+            (true, false) => template::YEAR,
+            (true, true) => template::YEAR_COMPACT,
+            (false, false) => template::DEFAULT,
+            (false, true) => template::COMPACT,
+        };
+        let template = Template::resolve(self.template.as_deref(), default)?;
+        let mut task = Task {
+            cmd: self,
+            template,
+            seq: Cell::new(0),
+        };
         task.run()
     }
 }
@@ -43,6 +63,8 @@ impl Cmd for TidyupCommand {
 // ==== TASK ====
 struct Task {
     cmd: TidyupCommand,
+    template: Template,
+    seq: Cell<usize>,
 }
 
 impl Task {
@@ -70,7 +92,7 @@ impl Task {
         let mut file_num = files.len() as i32;
         for f in &files {
             self.file(dir, dest, f, level, file_num)?;
-            file_num = file_num - 1;
+            file_num -= 1;
         }
         Ok(())
     }
@@ -107,27 +129,23 @@ impl Task {
         }
 
         let meta = meta_res.unwrap();
-        let meta = if self.cmd.exif {
+        let mut meta = if self.cmd.exif {
             meta.update_from_exif(path_str)
         } else {
             meta
         };
+        if self.template.needs_exif() {
+            meta.fill_from_exif(path_str);
+        }
+        if meta.source.is_fallback() {
+            println!("F,{order},DATE,{path_str},{}", meta.source);
+        }
 
         let cmd = &self.cmd;
         let date_str = meta.to_date();
 
-        let new_name = if cmd.compact {
-            meta.to_compact_name()
-        } else {
-            meta.to_name()
-        };
-
-        let dest_str = if cmd.year {
-            let year_str = date_str[0..4].to_string();
-            format!("{year_str}/{date_str}/{new_name}.{}", meta.ext)
-        } else {
-            format!("{date_str}/{new_name}.{}", meta.ext)
-        };
+        self.seq.set(self.seq.get() + 1);
+        let dest_str = self.template.render(&meta, self.seq.get());
 
         let dest_path = dest.join(&dest_str);
         let full_dest = dest_path.to_str().unwrap();
@@ -152,7 +170,7 @@ impl Task {
             if !parent.exists() {
                 std::fs::create_dir_all(parent).unwrap();
                 if cmd.touch {
-                    crate::core::touch::touch_form_0(parent.to_str().unwrap(), &date_str).unwrap();
+                    crate::core::touch::touch_form_0(parent.to_str().unwrap(), &date_str, &cmd.tz)?;
                 }
             }
         }
@@ -166,7 +184,10 @@ impl Task {
         };
 
         if cmd.touch {
-            crate::core::touch::touch(dest_path.to_str().unwrap(), meta.to_systemtime())?;
+            match meta.to_systemtime(&cmd.tz) {
+                Some(time) => crate::core::touch::touch(dest_path.to_str().unwrap(), time)?,
+                None => println!("F,{order},NOTIME,{full_dest},{}", meta.to_datetime()),
+            }
         }
         println!(
             "F,{order},MOVE,{path_str},{full_dest},{}",